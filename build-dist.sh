@@ -10,6 +10,8 @@ cp /usr/lib/libSDL2-2.0.so.0 dist
 cp /usr/lib/libSDL2_ttf-2.0.so.0 dist
 cp /usr/lib/libSDL2_image-2.0.so.0 dist
 cp /usr/lib/libSDL2_mixer-2.0.so.0 dist
-cp -r data dist
+# Only the files in the asset manifest are packed, sources like sprites.kra stay out
+mkdir dist/data
+target/release/nihil_chroma --pack dist/data/assets.pak
 
 zip -r9 nihil_chroma.zip dist/
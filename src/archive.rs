@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use bytes::{fnv1a, push_u16, push_u32, push_u64};
+use lz;
+
+// Identifies the file and the version of the format
+const MAGIC: &[u8; 8] = b"NCPACK01";
+
+const STORED: u8 = 0;
+const COMPRESSED: u8 = 1;
+
+/// Where a file is in an archive and how it is stored
+#[derive(Clone, Debug)]
+struct Entry {
+    method: u8,
+    // From the start of the archive
+    offset: u64,
+    packed_size: u32,
+    size: u32,
+    // FNV-1a hash of the contents, to notice damaged archives
+    hash: u64,
+}
+
+/// How much smaller packing made the files
+pub struct PackStats {
+    pub files: usize,
+    pub size: u64,
+    pub packed_size: u64,
+}
+
+fn read_bytes<R: Read>(reader: &mut R, count: usize) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![0; count];
+    reader.read_exact(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+fn read_u64<R: Read>(reader: &mut R, size: usize) -> Result<u64, String> {
+    let bytes = read_bytes(reader, size)?;
+    Ok(bytes.iter().rev().fold(0, |value, &byte| value << 8 | byte as u64))
+}
+
+/// Writes `files`, given by name and contents, into a new archive at `path`.
+/// Files that don't get smaller are stored as they are
+pub fn write(path: &Path, files: &[(String, Vec<u8>)]) -> Result<PackStats, String> {
+    let packed = files.iter()
+        .map(|(name, contents)| {
+            let compressed = lz::compress(contents);
+            if compressed.len() < contents.len() {
+                (name, COMPRESSED, compressed, contents)
+            } else {
+                (name, STORED, contents.clone(), contents)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let index_size = packed.iter()
+        .map(|&(name, ..)| 2 + name.len() + 1 + 8 + 4 + 4 + 8)
+        .sum::<usize>();
+    let mut offset = (MAGIC.len() + 4 + index_size) as u64;
+
+    let mut header = MAGIC.to_vec();
+    push_u32(&mut header, packed.len() as u32);
+    for &(name, method, ref data, contents) in &packed {
+        push_u16(&mut header, name.len() as u16);
+        header.extend_from_slice(name.as_bytes());
+        header.push(method);
+        push_u64(&mut header, offset);
+        push_u32(&mut header, data.len() as u32);
+        push_u32(&mut header, contents.len() as u32);
+        push_u64(&mut header, fnv1a(contents));
+        offset += data.len() as u64;
+    }
+
+    // Written next to the old archive first so that a failed write leaves it alone
+    let temp_path = path.with_extension("pak.tmp");
+    {
+        let mut file = File::create(&temp_path).map_err(|e| format!("{}: {}", temp_path.display(), e))?;
+        file.write_all(&header).map_err(|e| e.to_string())?;
+        for (_, _, data, _) in &packed {
+            file.write_all(data).map_err(|e| e.to_string())?;
+        }
+        file.sync_all().map_err(|e| e.to_string())?;
+    }
+    fs::rename(&temp_path, path).map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    Ok(PackStats {
+        files: packed.len(),
+        size: packed.iter().map(|&(_, _, _, contents)| contents.len() as u64).sum(),
+        packed_size: offset,
+    })
+}
+
+/// An archive made by `write`. Only the index is kept in memory, files are read
+/// from disk when they are asked for
+pub struct Archive {
+    path: PathBuf,
+    entries: HashMap<String, Entry>,
+}
+
+impl Archive {
+    pub fn open(path: &Path) -> Result<Archive, String> {
+        let mut file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut index = || -> Result<HashMap<String, Entry>, String> {
+            if &read_bytes(&mut file, MAGIC.len())?[..] != MAGIC {
+                return Err("not an asset archive, or one made by another version".to_string());
+            }
+            let count = read_u64(&mut file, 4)?;
+            let mut entries = HashMap::new();
+            for _ in 0..count {
+                let name_length = read_u64(&mut file, 2)? as usize;
+                let name = String::from_utf8(read_bytes(&mut file, name_length)?)
+                    .map_err(|_| "file name is not valid UTF-8".to_string())?;
+                let entry = Entry {
+                    method: read_bytes(&mut file, 1)?[0],
+                    offset: read_u64(&mut file, 8)?,
+                    packed_size: read_u64(&mut file, 4)? as u32,
+                    size: read_u64(&mut file, 4)? as u32,
+                    hash: read_u64(&mut file, 8)?,
+                };
+                entries.insert(name, entry);
+            }
+            Ok(entries)
+        };
+        let entries = index().map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(Archive { path: path.to_path_buf(), entries })
+    }
+
+    /// The contents of a file, None if the archive doesn't have it. Names are
+    /// paths relative to the asset root with `/` between directories
+    pub fn read(&self, name: &str) -> Result<Option<Vec<u8>>, String> {
+        let entry = match self.entries.get(name) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        let read = || -> Result<Vec<u8>, String> {
+            let mut file = File::open(&self.path).map_err(|e| e.to_string())?;
+            file.seek(SeekFrom::Start(entry.offset)).map_err(|e| e.to_string())?;
+            let data = read_bytes(&mut file, entry.packed_size as usize)?;
+            let contents = match entry.method {
+                STORED => data,
+                COMPRESSED => lz::decompress(&data, entry.size as usize)?,
+                method => return Err(format!("unknown packing method {}", method)),
+            };
+            if contents.len() != entry.size as usize || fnv1a(&contents) != entry.hash {
+                return Err("the contents are damaged".to_string());
+            }
+            Ok(contents)
+        };
+        read().map(Some).map_err(|e| format!("{} in {}: {}", name, self.path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::process;
+
+    fn archive_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("nihil-chroma-{}-{}.pak", name, process::id()))
+    }
+
+    fn files() -> Vec<(String, Vec<u8>)> {
+        vec!(
+            ("waves.txt".to_string(), b"[wave]\nballs = 10\n".iter().cycle().take(1000).cloned().collect()),
+            ("fonts/empty.ttf".to_string(), vec!()),
+            ("sounds/noise.wav".to_string(), (0..200).map(|i| (i * 151 % 256) as u8).collect()),
+        )
+    }
+
+    #[test]
+    fn files_read_back() {
+        let path = archive_path("read");
+        let stats = write(&path, &files()).unwrap();
+        assert_eq!(stats.files, 3);
+        assert_eq!(stats.size, 1200);
+        assert_eq!(stats.packed_size, fs::metadata(&path).unwrap().len());
+
+        let archive = Archive::open(&path).unwrap();
+        for (name, contents) in files() {
+            assert_eq!(archive.read(&name), Ok(Some(contents)));
+        }
+        assert_eq!(archive.read("missing.txt"), Ok(None));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn damaged_files_are_reported() {
+        let path = archive_path("damaged");
+        write(&path, &files()).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        // The last byte belongs to the last file
+        *bytes.last_mut().unwrap() ^= 1;
+        fs::write(&path, bytes).unwrap();
+
+        let archive = Archive::open(&path).unwrap();
+        let error = archive.read("sounds/noise.wav").unwrap_err();
+        assert!(error.ends_with("the contents are damaged"), "{}", error);
+        assert!(archive.read("waves.txt").unwrap().is_some());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn other_files_are_not_archives() {
+        let path = archive_path("other");
+        fs::write(&path, "[wave]\n").unwrap();
+        assert!(Archive::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
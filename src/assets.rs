@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use archive::{self, Archive, PackStats};
+use config::{self, Section};
+use paths;
+use ron::{self, Value};
+
+// Lists the assets and marks the directory that it is in as an asset root
+pub const MANIFEST: &str = "assets.ron";
+// The assets packed into one file by `--pack`, read instead of the loose files
+// when it is there
+pub const ARCHIVE: &str = "assets.pak";
+// Environment variable with the asset root, for installs that keep it elsewhere
+const ROOT_VARIABLE: &str = "NIHIL_CHROMA_ASSETS";
+
+/// Every file that the game loads, by the name that the code and the other data
+/// files use for it. Paths are relative to the asset root
+#[derive(Clone, Debug, Default)]
+pub struct Manifest {
+    pub textures: HashMap<String, String>,
+    // Groups of textures that the game picks from at random
+    pub sprite_sets: HashMap<String, Vec<String>>,
+    pub fonts: HashMap<String, String>,
+    pub sounds: HashMap<String, String>,
+    // Data files with definitions and tuning
+    pub config: HashMap<String, String>,
+    pub scripts: Vec<String>,
+}
+
+fn string_list(value: &Value) -> Result<Vec<String>, String> {
+    value.as_list()?.iter()
+        .map(|item| item.as_str().map(String::from))
+        .collect()
+}
+
+/// A map from names to something, missing maps are empty
+fn named<T, F>(manifest: &Value, field: &str, item: F) -> Result<HashMap<String, T>, String>
+    where F: Fn(&Value) -> Result<T, String>
+{
+    let entries = match manifest.field(field)? {
+        Some(value) => value.as_map().map_err(|e| format!("{}: {}", field, e))?,
+        None => return Ok(HashMap::new()),
+    };
+    entries.iter()
+        .map(|(name, value)| {
+            let name = name.as_str().map_err(|e| format!("{}: {}", field, e))?;
+            let value = item(value).map_err(|e| format!("{}: {}: {}", field, name, e))?;
+            Ok((name.to_string(), value))
+        })
+        .collect()
+}
+
+impl Manifest {
+    pub fn parse(text: &str) -> Result<Manifest, String> {
+        let manifest = ron::parse(text)?;
+        let as_string = |value: &Value| value.as_str().map(String::from);
+        Ok(Manifest {
+            textures: named(&manifest, "textures", as_string)?,
+            sprite_sets: named(&manifest, "sprite_sets", string_list)?,
+            fonts: named(&manifest, "fonts", as_string)?,
+            sounds: named(&manifest, "sounds", as_string)?,
+            config: named(&manifest, "config", as_string)?,
+            scripts: match manifest.field("scripts")? {
+                Some(scripts) => string_list(scripts).map_err(|e| format!("scripts: {}", e))?,
+                None => vec!(),
+            },
+        })
+    }
+}
+
+/// The manifest along with the directory that its paths are relative to. Files
+/// are read from the archive in that directory if there is one, and from the
+/// directory itself otherwise
+pub struct Assets {
+    pub root: PathBuf,
+    pub manifest: Manifest,
+    archive: Option<Archive>,
+}
+
+/// The name of a file in the archive, which is its path relative to the root
+/// with `/` between directories
+fn archive_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl Assets {
+    /// Loads the manifest from `root`. The archive is left alone unless
+    /// `use_archive` is set, so that edits to the loose files show up
+    pub fn load(root: &Path, use_archive: bool) -> Result<Assets, String> {
+        let archive_path = root.join(ARCHIVE);
+        let archive = if use_archive && archive_path.is_file() {
+            Some(Archive::open(&archive_path)?)
+        } else {
+            None
+        };
+
+        let mut assets = Assets { root: root.to_path_buf(), manifest: Manifest::default(), archive };
+        let path = assets.path(MANIFEST);
+        let text = assets.read_to_string(&path)?;
+        assets.manifest = Manifest::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(assets)
+    }
+
+    /// The contents of a file from `path`, from the archive if it has it
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, String> {
+        let packed = match (&self.archive, Path::new(path).strip_prefix(&self.root)) {
+            (Some(archive), Ok(relative)) => archive.read(&archive_name(relative))?,
+            _ => None,
+        };
+        match packed {
+            Some(contents) => Ok(contents),
+            None => fs::read(path).map_err(|e| format!("{}: {}", path, e)),
+        }
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Result<String, String> {
+        String::from_utf8(self.read(path)?).map_err(|_| format!("{}: not valid UTF-8", path))
+    }
+
+    /// Reads a data file in the format of the `config` module
+    pub fn load_config(&self, path: &str) -> Result<Vec<Section>, String> {
+        config::parse(&self.read_to_string(path)?).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    /// Where a file from the manifest is
+    pub fn path(&self, file: &str) -> String {
+        self.root.join(file).to_string_lossy().into_owned()
+    }
+
+    fn find(&self, kind: &str, files: &HashMap<String, String>, name: &str) -> Result<String, String> {
+        files.get(name)
+            .map(|file| self.path(file))
+            .ok_or_else(|| format!("there is no {} called {} in {}", kind, name, MANIFEST))
+    }
+
+    pub fn texture(&self, name: &str) -> Result<String, String> {
+        self.find("texture", &self.manifest.textures, name)
+    }
+
+    pub fn font(&self, name: &str) -> Result<String, String> {
+        self.find("font", &self.manifest.fonts, name)
+    }
+
+    pub fn sound(&self, name: &str) -> Result<String, String> {
+        self.find("sound", &self.manifest.sounds, name)
+    }
+
+    pub fn config(&self, name: &str) -> Result<String, String> {
+        self.find("config file", &self.manifest.config, name)
+    }
+
+    pub fn sprite_set(&self, name: &str) -> Result<Vec<String>, String> {
+        self.manifest.sprite_sets.get(name)
+            .map(|files| files.iter().map(|file| self.path(file)).collect())
+            .ok_or_else(|| format!("there is no sprite set called {} in {}", name, MANIFEST))
+    }
+
+    pub fn scripts(&self) -> Vec<String> {
+        self.manifest.scripts.iter().map(|file| self.path(file)).collect()
+    }
+
+    /// Where every file in the manifest is, each listed once
+    pub fn files(&self) -> Vec<String> {
+        let manifest = &self.manifest;
+        let mut files = manifest.textures.values()
+            .chain(manifest.sprite_sets.values().flat_map(|set| set.iter()))
+            .chain(manifest.fonts.values())
+            .chain(manifest.sounds.values())
+            .chain(manifest.config.values())
+            .chain(manifest.scripts.iter())
+            .map(|file| self.path(file))
+            .collect::<Vec<_>>();
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    /// Packs the manifest and every file in it into an archive at `path`.
+    /// Files that aren't in the manifest, like the sources of the sprites, are
+    /// left out
+    pub fn pack(&self, path: &Path) -> Result<PackStats, String> {
+        let mut files = vec!(self.path(MANIFEST));
+        files.extend(self.files());
+        let contents = files.iter()
+            .map(|file| {
+                let name = Path::new(file).strip_prefix(&self.root)
+                    .map(archive_name)
+                    .map_err(|_| format!("{} is outside of {}", file, self.root.display()))?;
+                Ok((name, self.read(file)?))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        archive::write(path, &contents)
+    }
+}
+
+fn is_root(dir: &Path) -> bool {
+    dir.join(MANIFEST).is_file() || dir.join(ARCHIVE).is_file()
+}
+
+/// Finds the directory with the assets. `--assets <dir>` or the
+/// NIHIL_CHROMA_ASSETS variable pick one, otherwise the first of these with a
+/// manifest or an archive is used:
+///
+/// - `data` next to the executable, where `build-dist.sh` puts it
+/// - `data` in the working directory, for `cargo run` in the repository
+/// - `nihil-chroma/data` in the XDG data directories
+pub fn find_root(args: &[String]) -> Result<PathBuf, String> {
+    let chosen = args.iter()
+        .position(|arg| arg == "--assets")
+        .and_then(|i| args.get(i + 1))
+        .map(|dir| ("--assets", PathBuf::from(dir)))
+        .or_else(|| env::var_os(ROOT_VARIABLE).map(|dir| (ROOT_VARIABLE, PathBuf::from(dir))));
+    // Roots that were asked for are never swapped for another one
+    if let Some((source, dir)) = chosen {
+        return if is_root(&dir) {
+            Ok(dir)
+        } else {
+            Err(format!("{} is set to {}, which has no {} or {}", source, dir.display(), MANIFEST, ARCHIVE))
+        };
+    }
+
+    let mut candidates = vec!();
+    if let Some(dir) = env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        candidates.push(dir.join("data"));
+    }
+    candidates.push(PathBuf::from("data"));
+    if let Ok(dir) = paths::data_dir() {
+        candidates.push(dir.join("data"));
+    }
+    candidates.extend(paths::system_data_dirs().into_iter().map(|dir| dir.join("data")));
+
+    candidates.iter()
+        .find(|dir| is_root(dir))
+        .cloned()
+        .ok_or_else(|| {
+            let searched = candidates.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>();
+            format!("none of these have an {} or {}: {}", MANIFEST, ARCHIVE, searched.join(", "))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("data")
+    }
+
+    #[test]
+    fn parses_the_manifest() {
+        let manifest = Manifest::parse(include_str!("../data/assets.ron")).unwrap();
+        assert_eq!(manifest.textures.get("player").map(String::as_str), Some("good.png"));
+        assert!(!manifest.sprite_sets["obamas"].is_empty());
+        assert!(manifest.fonts.contains_key("main"));
+        for name in &["balls", "movement", "music", "sounds", "waves"] {
+            assert!(manifest.config.contains_key(*name), "no config file called {}", name);
+        }
+    }
+
+    #[test]
+    fn manifest_files_exist() {
+        let assets = Assets::load(&data_dir(), false).unwrap();
+        for file in assets.files() {
+            assert!(Path::new(&file).is_file(), "{} is missing", file);
+        }
+    }
+
+    #[test]
+    fn manifest_errors_name_the_field() {
+        assert_eq!(
+            Manifest::parse("(textures: {\"player\": 1})").err(),
+            Some("textures: player: expected a string, found a number".to_string())
+        );
+        assert_eq!(
+            Manifest::parse("(scripts: \"a\")").err(),
+            Some("scripts: expected a list, found a string".to_string())
+        );
+    }
+}
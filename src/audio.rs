@@ -0,0 +1,488 @@
+extern crate specs;
+extern crate rand;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use rand::Rng;
+use sdl2::mixer::{self, Channel, Chunk, Group, LoaderRWops};
+use sdl2::rwops::RWops;
+
+use assets::Assets;
+use bytes::{push_u16, push_u32};
+use components::BallType;
+use config::Section;
+use spatial::{self, StereoPosition};
+use spawner::{BallEvent, BallEvents};
+
+const SFX_CHANNELS: i32 = 12;
+const UI_CHANNELS: i32 = 4;
+// Two music tracks play at the same time while crossfading
+const MUSIC_CHANNELS: i32 = 2;
+// Looping sounds of balls get their own channels so that other sounds can't
+// take them over
+const LOOP_GROUP: Group = Group(4);
+// Number of differently pitched copies made of each sound with pitch variation
+const PITCH_VARIANTS: usize = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SoundGroup {
+    Sfx,
+    Ui,
+    Music,
+}
+
+impl SoundGroup {
+    pub fn all() -> [SoundGroup; 3] {
+        [SoundGroup::Sfx, SoundGroup::Ui, SoundGroup::Music]
+    }
+
+    pub fn from_name(name: &str) -> Option<SoundGroup> {
+        SoundGroup::all().iter().cloned().find(|group| group.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SoundGroup::Sfx => "sfx",
+            SoundGroup::Ui => "ui",
+            SoundGroup::Music => "music",
+        }
+    }
+
+    pub fn mixer_group(&self) -> Group {
+        match *self {
+            SoundGroup::Sfx => Group(1),
+            SoundGroup::Ui => Group(2),
+            SoundGroup::Music => Group(3),
+        }
+    }
+}
+
+/// A named sound from `data/sounds.txt`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoundInfo {
+    pub file: String,
+    pub group: SoundGroup,
+    pub volume: f32,
+    // How far the pitch and volume may randomly differ from the original, as a
+    // fraction. 0.1 means anywhere between 90% and 110%
+    pub pitch_variation: f32,
+    pub volume_variation: f32,
+}
+
+impl SoundInfo {
+    /// Sounds from the asset manifest that aren't in the sound list
+    fn plain_file(file: &str) -> SoundInfo {
+        SoundInfo {
+            file: file.to_string(),
+            group: SoundGroup::Sfx,
+            volume: 1.,
+            pitch_variation: 0.,
+            volume_variation: 0.,
+        }
+    }
+
+    fn from_section(section: &Section, assets: &Assets) -> Result<SoundInfo, String> {
+        let group = section.get_str("group").unwrap_or("sfx");
+        Ok(SoundInfo {
+            file: assets.sound(section.get_str("asset").ok_or("sound without an asset")?)?,
+            // The music channels belong to the music director
+            group: match SoundGroup::from_name(group) {
+                Some(SoundGroup::Music) | None => {
+                    return Err(format!("unknown sound group {}, expected sfx or ui", group));
+                }
+                Some(group) => group,
+            },
+            volume: section.get_or("volume", 1.)?,
+            pitch_variation: section.get_or("pitch_variation", 0.)?,
+            volume_variation: section.get_or("volume_variation", 0.)?,
+        })
+    }
+}
+
+/// The sounds and group volumes defined in a sound list
+pub struct SoundList {
+    pub sounds: HashMap<String, SoundInfo>,
+    pub volumes: HashMap<SoundGroup, f32>,
+    // Every sound file in the asset manifest, by name
+    pub files: HashMap<String, String>,
+}
+
+pub fn load_sounds(path: &str, assets: &Assets) -> Result<SoundList, String> {
+    let sections = assets.load_config(path)?;
+
+    let mut sounds = HashMap::new();
+    for section in sections.iter().filter(|section| section.name == "sound") {
+        let name = section.get_str("name")
+            .ok_or_else(|| format!("{}: sound without a name", path))?;
+        let info = SoundInfo::from_section(section, assets).map_err(|e| format!("{}: {}: {}", path, name, e))?;
+        sounds.insert(name.to_string(), info);
+    }
+
+    let mut volumes = HashMap::new();
+    for section in sections.iter().filter(|section| section.name == "volume") {
+        for &group in &SoundGroup::all() {
+            if let Some(volume) = section.get(group.name()).map_err(|e| format!("{}: {}", path, e))? {
+                volumes.insert(group, volume);
+            }
+        }
+    }
+
+    let files = assets.manifest.sounds.iter()
+        .map(|(name, file)| (name.clone(), assets.path(file)))
+        .collect();
+    Ok(SoundList { sounds, volumes, files })
+}
+
+/// Sounds that systems want played this frame, by name. Played and
+/// emptied by the `AudioManager` in the main loop
+pub struct SoundQueue(pub Vec<String>);
+
+/// 16 bit PCM wav data that can be resampled to change its pitch
+struct Pcm {
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<i16>,
+}
+
+impl Pcm {
+    /// Parses a wav file. Returns None for anything but uncompressed 16 bit audio
+    fn parse(bytes: &[u8]) -> Option<Pcm> {
+        let u16_at = |i: usize| bytes.get(i..i + 2).map(|b| b[0] as u16 | (b[1] as u16) << 8);
+        let u32_at = |i: usize| {
+            bytes.get(i..i + 4).map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+        };
+
+        if bytes.get(0..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"WAVE") {
+            return None;
+        }
+
+        let mut format = None;
+        let mut data = None;
+        let mut pos = 12;
+        while let (Some(id), Some(size)) = (bytes.get(pos..pos + 4), u32_at(pos + 4)) {
+            let body = pos + 8;
+            match id {
+                b"fmt " => format = Some((u16_at(body)?, u16_at(body + 2)?, u32_at(body + 4)?, u16_at(body + 14)?)),
+                b"data" => data = bytes.get(body..body + size as usize),
+                _ => {}
+            }
+            // Chunks are padded to an even size
+            pos = body + size as usize + (size as usize & 1);
+        }
+
+        match (format, data) {
+            (Some((1, channels, sample_rate, 16)), Some(data)) if channels > 0 => Some(Pcm {
+                channels,
+                sample_rate,
+                samples: data.chunks(2)
+                    .filter(|pair| pair.len() == 2)
+                    .map(|pair| (pair[0] as u16 | (pair[1] as u16) << 8) as i16)
+                    .collect(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Plays the sound `pitch` times faster, which raises the pitch by as much
+    fn resampled(&self, pitch: f32) -> Pcm {
+        let channels = self.channels as usize;
+        let frames = self.samples.len() / channels;
+        let new_frames = (frames as f32 / pitch) as usize;
+
+        let mut samples = Vec::with_capacity(new_frames * channels);
+        for frame in 0..new_frames {
+            let source = frame as f32 * pitch;
+            let before = (source as usize).min(frames - 1);
+            let after = (before + 1).min(frames - 1);
+            let t = source - before as f32;
+
+            for channel in 0..channels {
+                let a = self.samples[before * channels + channel] as f32;
+                let b = self.samples[after * channels + channel] as f32;
+                samples.push((a + (b - a) * t) as i16);
+            }
+        }
+
+        Pcm { channels: self.channels, sample_rate: self.sample_rate, samples }
+    }
+
+    fn to_wav(&self) -> Vec<u8> {
+        let data_size = self.samples.len() as u32 * 2;
+        let block_align = self.channels * 2;
+
+        let mut bytes = Vec::with_capacity(44 + data_size as usize);
+        bytes.extend_from_slice(b"RIFF");
+        push_u32(&mut bytes, 36 + data_size);
+        bytes.extend_from_slice(b"WAVEfmt ");
+        push_u32(&mut bytes, 16);
+        push_u16(&mut bytes, 1);
+        push_u16(&mut bytes, self.channels);
+        push_u32(&mut bytes, self.sample_rate);
+        push_u32(&mut bytes, self.sample_rate * block_align as u32);
+        push_u16(&mut bytes, block_align);
+        push_u16(&mut bytes, 16);
+        bytes.extend_from_slice(b"data");
+        push_u32(&mut bytes, data_size);
+        for &sample in &self.samples {
+            push_u16(&mut bytes, sample as u16);
+        }
+        bytes
+    }
+}
+
+/// Decodes a sound file that is already in memory. The mixer makes its own copy
+/// of the samples so `bytes` can be dropped afterwards
+pub fn chunk_from_bytes(bytes: &[u8]) -> Result<Chunk, String> {
+    let rwops = RWops::from_bytes(bytes)?;
+    rwops.load_wav()
+}
+
+/// Loads, caches and plays sound effects. Sounds are played on the channels of
+/// their group and with the volume of that group. Music is played by the
+/// `MusicDirector` on the channels of the music group.
+///
+/// Chunks can't be shared between threads, so this lives in the main loop and
+/// systems ask for sounds through the `SoundQueue` resource
+pub struct AudioManager {
+    sounds: HashMap<String, SoundInfo>,
+    files: HashMap<String, String>,
+    volumes: HashMap<SoundGroup, f32>,
+    // Volumes that the player picked, on top of the ones from the sound list
+    user_volumes: HashMap<SoundGroup, f32>,
+    pub master_volume: f32,
+    // Chunks of a file, at increasing pitch if the sound has pitch variation
+    cache: HashMap<String, Vec<Chunk>>,
+    // Sound and channel of the looping sounds that are playing, by entity id
+    loops: HashMap<u32, (String, Channel)>,
+    // There is no sound device, so nothing is loaded or played
+    silent: bool,
+    assets: Rc<Assets>,
+}
+
+impl AudioManager {
+    /// Sets up the mixer channels. The mixer must already be open
+    pub fn new(sound_list: SoundList, assets: Rc<Assets>) -> AudioManager {
+        let ui_start = SFX_CHANNELS;
+        let music_start = ui_start + UI_CHANNELS;
+        let loop_start = music_start + MUSIC_CHANNELS;
+        let loop_channels = spatial::MAX_LOOPS as i32;
+        mixer::allocate_channels(loop_start + loop_channels);
+        SoundGroup::Sfx.mixer_group().add_channels_range(0, ui_start - 1);
+        SoundGroup::Ui.mixer_group().add_channels_range(ui_start, music_start - 1);
+        SoundGroup::Music.mixer_group().add_channels_range(music_start, loop_start - 1);
+        LOOP_GROUP.add_channels_range(loop_start, loop_start + loop_channels - 1);
+
+        AudioManager { silent: false, ..AudioManager::silent(sound_list, assets) }
+    }
+
+    /// An audio manager that ignores all sounds, for when the mixer could not
+    /// be opened
+    pub fn silent(sound_list: SoundList, assets: Rc<Assets>) -> AudioManager {
+        AudioManager {
+            sounds: sound_list.sounds,
+            files: sound_list.files,
+            volumes: sound_list.volumes,
+            user_volumes: HashMap::new(),
+            master_volume: 1.,
+            cache: HashMap::new(),
+            loops: HashMap::new(),
+            silent: true,
+            assets,
+        }
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.silent
+    }
+
+    /// Replaces the sound list after it changed. Sounds are loaded again the
+    /// next time they are played
+    pub fn set_sound_list(&mut self, sound_list: SoundList) {
+        for (_, (_, channel)) in self.loops.drain() {
+            channel.halt();
+        }
+        self.cache.clear();
+        self.sounds = sound_list.sounds;
+        self.files = sound_list.files;
+        self.volumes = sound_list.volumes;
+    }
+
+    /// Loads a sound file again after it changed. Looping sounds that use it
+    /// start over. Returns false if the file wasn't loaded before
+    pub fn reload(&mut self, file: &str) -> Result<bool, String> {
+        if !self.cache.contains_key(file) {
+            return Ok(false);
+        }
+
+        let stopped = self.loops.iter()
+            .filter(|&(_, &(ref sound, _))| self.info(sound).map(|info| info.file == file).unwrap_or(false))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in stopped {
+            self.loops.remove(&id).unwrap().1.halt();
+        }
+
+        // Pitch variants are made with the settings of the first sound that uses the file
+        let info = self.sounds.values()
+            .find(|info| info.file == file)
+            .cloned()
+            .unwrap_or_else(|| SoundInfo::plain_file(file));
+        self.cache.remove(file);
+        self.load(&info)?;
+        Ok(true)
+    }
+
+    pub fn volume(&self, group: SoundGroup) -> f32 {
+        self.volumes.get(&group).cloned().unwrap_or(1.)
+    }
+
+    /// Sets the volume that the player wants for a group. Sounds that are
+    /// already playing keep their volume
+    pub fn set_volume(&mut self, group: SoundGroup, volume: f32) {
+        self.user_volumes.insert(group, volume);
+    }
+
+    /// The volume that a group ends up playing at, including the master volume
+    pub fn group_volume(&self, group: SoundGroup) -> f32 {
+        let user_volume = self.user_volumes.get(&group).cloned().unwrap_or(1.);
+        self.master_volume * user_volume * self.volume(group)
+    }
+
+    fn info(&self, sound: &str) -> Result<SoundInfo, String> {
+        self.sounds.get(sound).cloned()
+            .or_else(|| self.files.get(sound).map(|file| SoundInfo::plain_file(file)))
+            .ok_or_else(|| format!("there is no sound called {}", sound))
+    }
+
+    /// Loads a sound into the cache ahead of time
+    pub fn preload(&mut self, sound: &str) -> Result<(), String> {
+        let info = self.info(sound)?;
+        self.load(&info)
+    }
+
+    pub fn preload_all(&mut self) -> Result<(), String> {
+        let names = self.sounds.keys().cloned().collect::<Vec<_>>();
+        for name in names {
+            self.preload(&name)?;
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, info: &SoundInfo) -> Result<(), String> {
+        if self.silent || self.cache.contains_key(&info.file) {
+            return Ok(());
+        }
+
+        let bytes = self.assets.read(&info.file)?;
+
+        // Only plain wav files can be pitched, everything else is played as is
+        let chunks = match Pcm::parse(&bytes) {
+            Some(ref pcm) if info.pitch_variation > 0. && !pcm.samples.is_empty() => (0..PITCH_VARIANTS)
+                .map(|i| {
+                    let step = i as f32 / (PITCH_VARIANTS - 1) as f32 * 2. - 1.;
+                    let wav = pcm.resampled(1. + step * info.pitch_variation).to_wav();
+                    chunk_from_bytes(&wav)
+                })
+                .collect::<Result<Vec<_>, String>>(),
+            _ => chunk_from_bytes(&bytes).map(|chunk| vec!(chunk)),
+        }.map_err(|e| format!("{}: {}", info.file, e))?;
+
+        self.cache.insert(info.file.clone(), chunks);
+        Ok(())
+    }
+
+    /// Plays a sound from the sound list, or a sound from the asset manifest
+    /// with default settings
+    pub fn play(&mut self, sound: &str) -> Result<Option<Channel>, String> {
+        self.play_at(sound, None)
+    }
+
+    /// Plays a sound, from a position around the player if one is given
+    pub fn play_at(&mut self, sound: &str, position: Option<StereoPosition>) -> Result<Option<Channel>, String> {
+        if self.silent {
+            return Ok(None);
+        }
+        let info = self.info(sound)?;
+        self.load(&info)?;
+
+        let mut rng = rand::thread_rng();
+        let chunks = &self.cache[&info.file];
+        let chunk = &chunks[rng.gen_range(0, chunks.len())];
+
+        let group = info.group.mixer_group();
+        let channel = match group.find_available().or_else(|| group.find_oldest()) {
+            Some(channel) => channel,
+            // Every channel is busy with something more important
+            None => return Ok(None),
+        };
+
+        let variation = 1. + rng.gen_range(-1., 1.) * info.volume_variation;
+        let volume = self.group_volume(info.group) * info.volume * variation;
+
+        // Channels keep their position from whatever they played last
+        match position {
+            Some(position) => channel.set_position(position.angle, position.distance)?,
+            None => channel.unset_position()?,
+        }
+        let channel = channel.play(chunk, 0)?;
+        channel.set_volume((volume.max(0.).min(1.) * mixer::MAX_VOLUME as f32) as i32);
+        Ok(Some(channel))
+    }
+
+    /// Makes the looping sounds in `loops` play from their positions, starting
+    /// the new ones and stopping the ones that are no longer in the list
+    pub fn update_loops(&mut self, loops: &[(u32, String, StereoPosition)]) -> Result<(), String> {
+        if self.silent {
+            return Ok(());
+        }
+        // Entity ids get reused, so a loop with another sound is a new one
+        let stopped = self.loops.iter()
+            .filter(|&(id, &(ref playing, _))| {
+                !loops.iter().any(|&(loop_id, ref sound, _)| loop_id == *id && sound == playing)
+            })
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        for id in stopped {
+            self.loops.remove(&id).unwrap().1.halt();
+        }
+
+        for &(id, ref sound, position) in loops {
+            if !self.loops.contains_key(&id) {
+                let info = self.info(sound)?;
+                self.load(&info)?;
+
+                let channel = match LOOP_GROUP.find_available() {
+                    Some(channel) => channel,
+                    None => continue,
+                };
+                channel.set_position(position.angle, position.distance)?;
+                let channel = channel.play(&self.cache[&info.file][0], -1)?;
+                let volume = self.group_volume(info.group) * info.volume;
+                channel.set_volume((volume.max(0.).min(1.) * mixer::MAX_VOLUME as f32) as i32);
+                self.loops.insert(id, (sound.clone(), channel));
+            }
+
+            self.loops[&id].1.set_position(position.angle, position.distance)?;
+        }
+        Ok(())
+    }
+}
+
+/// Queues the collection sounds of balls
+pub struct BallSoundSystem {
+    pub sounds: HashMap<BallType, String>,
+}
+
+impl<'a> specs::System<'a> for BallSoundSystem {
+    type SystemData = (specs::Fetch<'a, BallEvents>, specs::FetchMut<'a, SoundQueue>);
+    fn run(&mut self, (ball_events, mut sound_queue): Self::SystemData) {
+        for event in &ball_events.0 {
+            if let BallEvent::Collected { ball_type, .. } = *event {
+                if let Some(sound) = self.sounds.get(&ball_type) {
+                    sound_queue.0.push(sound.clone());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+extern crate specs;
+
+use specs::Join;
+
+use components::{Transform, Velocity, BoundingCircle};
+use constants::*;
+use player::PlayerComponent;
+
+/// What happens when a player reaches the edge of the world
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EdgeMode {
+    // Stop at the edge
+    Clamp,
+    // Bounce back off the edge
+    Bounce,
+    // Come back in on the opposite side
+    Wrap,
+}
+
+impl EdgeMode {
+    pub fn from_name(name: &str) -> Option<EdgeMode> {
+        match name {
+            "clamp" => Some(EdgeMode::Clamp),
+            "bounce" => Some(EdgeMode::Bounce),
+            "wrap" => Some(EdgeMode::Wrap),
+            _ => None,
+        }
+    }
+}
+
+/// Resource with the edge rules of the current wave
+pub struct PlayerBounds(pub EdgeMode);
+
+/// Keeps one axis of a player inside 0..size. Returns the new position and velocity
+fn keep_inside(pos: f32, vel: f32, radius: f32, size: f32, mode: EdgeMode) -> (f32, f32) {
+    match mode {
+        EdgeMode::Clamp | EdgeMode::Bounce => {
+            let bounce = if mode == EdgeMode::Bounce { -1. } else { 0. };
+            if pos < radius {
+                (radius, if vel < 0. { vel * bounce } else { vel })
+            } else if pos > size - radius {
+                (size - radius, if vel > 0. { vel * bounce } else { vel })
+            } else {
+                (pos, vel)
+            }
+        }
+        // Wrapping happens once the center leaves the world
+        EdgeMode::Wrap => {
+            if pos < 0. {
+                (pos + size, vel)
+            } else if pos >= size {
+                (pos - size, vel)
+            } else {
+                (pos, vel)
+            }
+        }
+    }
+}
+
+pub struct BoundsSystem;
+
+impl<'a> specs::System<'a> for BoundsSystem {
+    type SystemData = (
+        specs::WriteStorage<'a, Transform>,
+        specs::WriteStorage<'a, Velocity>,
+        specs::ReadStorage<'a, BoundingCircle>,
+        specs::ReadStorage<'a, PlayerComponent>,
+        specs::Fetch<'a, PlayerBounds>,
+    );
+    fn run(&mut self, (mut transforms, mut velocities, bounding_circles, players, bounds): Self::SystemData) {
+        for (transform, velocity, bounding_circle, _) in
+            (&mut transforms, &mut velocities, &bounding_circles, &players).join()
+        {
+            let (x, vel_x) = keep_inside(
+                transform.pos.x, velocity.0.x, bounding_circle.radius, WORLD_SIZE.0 as f32, bounds.0
+            );
+            let (y, vel_y) = keep_inside(
+                transform.pos.y, velocity.0.y, bounding_circle.radius, WORLD_SIZE.1 as f32, bounds.0
+            );
+
+            transform.pos.x = x;
+            transform.pos.y = y;
+            velocity.0.x = vel_x;
+            velocity.0.y = vel_y;
+        }
+    }
+}
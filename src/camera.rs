@@ -0,0 +1,137 @@
+extern crate specs;
+
+use nalgebra::Vector2;
+
+use specs::NullStorage;
+
+use components::Transform;
+use cone::ViewCone;
+use constants::*;
+
+#[derive(Component, Default)]
+#[component(NullStorage)]
+/// Entities that are drawn at fixed screen coordinates, like the HUD
+pub struct ScreenSpace;
+
+/// Resource with the part of the world that is shown on screen
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Camera {
+    // World position shown in the middle of the screen
+    pub pos: Vector2<f32>,
+    pub zoom: f32,
+    // Radians that the view is turned clockwise
+    pub rotation: f64,
+    // Half size of the area around `pos` where the target can move without the
+    // camera following
+    pub deadzone: Vector2<f32>,
+    // How quickly the camera catches up with its target, per second
+    pub follow_speed: f32,
+    // Corners of the area the camera is not allowed to show anything outside of
+    pub bounds: Option<(Vector2<f32>, Vector2<f32>)>,
+    // Set by the `ShakeSystem` every frame, in screen pixels and radians
+    pub shake_offset: Vector2<f32>,
+    pub shake_rotation: f64,
+}
+
+fn rotate(v: Vector2<f32>, angle: f64) -> Vector2<f32> {
+    let (sin, cos) = (angle.sin() as f32, angle.cos() as f32);
+    Vector2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+fn screen_center() -> Vector2<f32> {
+    Vector2::new(RESOLUTION.0 as f32 / 2., RESOLUTION.1 as f32 / 2.)
+}
+
+impl Camera {
+    pub fn new(pos: Vector2<f32>) -> Camera {
+        Camera {
+            pos,
+            zoom: 1.,
+            rotation: 0.,
+            deadzone: Vector2::new(40., 40.),
+            follow_speed: 5.,
+            bounds: None,
+            shake_offset: Vector2::new(0., 0.),
+            shake_rotation: 0.,
+        }
+    }
+
+    /// A camera that maps world coordinates straight to the screen
+    pub fn screen() -> Camera {
+        Camera::new(screen_center())
+    }
+
+    fn view_rotation(&self) -> f64 {
+        self.rotation + self.shake_rotation
+    }
+
+    pub fn world_to_screen(&self, pos: Vector2<f32>) -> Vector2<f32> {
+        rotate(pos - self.pos, -self.view_rotation()) * self.zoom + screen_center() + self.shake_offset
+    }
+
+    pub fn screen_to_world(&self, pos: Vector2<f32>) -> Vector2<f32> {
+        rotate((pos - screen_center() - self.shake_offset) / self.zoom, self.view_rotation()) + self.pos
+    }
+
+    /// Where and how big a sprite with the world `transform` ends up on screen
+    pub fn transform_to_screen(&self, transform: &Transform) -> Transform {
+        Transform {
+            pos: self.world_to_screen(transform.pos),
+            angle: transform.angle - self.view_rotation(),
+            scale: transform.scale * self.zoom,
+        }
+    }
+
+    pub fn cone_to_screen(&self, cone: &ViewCone) -> ViewCone {
+        ViewCone {
+            origin: self.world_to_screen(cone.origin),
+            angle: cone.angle - self.view_rotation(),
+            size: cone.size,
+        }
+    }
+
+    /// Moves the camera towards `target`, keeping it inside the deadzone and the
+    /// camera inside its bounds
+    pub fn follow(&mut self, target: Vector2<f32>, frametime: f32) {
+        let offset = target - self.pos;
+        let outside_deadzone = Vector2::new(
+            offset.x - offset.x.max(-self.deadzone.x).min(self.deadzone.x),
+            offset.y - offset.y.max(-self.deadzone.y).min(self.deadzone.y),
+        );
+
+        self.pos += outside_deadzone * (self.follow_speed * frametime).min(1.);
+        self.keep_in_bounds();
+    }
+
+    fn keep_in_bounds(&mut self) {
+        if let Some((min, max)) = self.bounds {
+            let half_view = screen_center() / self.zoom;
+            self.pos.x = clamp_view(self.pos.x, half_view.x, min.x, max.x);
+            self.pos.y = clamp_view(self.pos.y, half_view.y, min.y, max.y);
+        }
+    }
+}
+
+/// Keeps a view of `half_view` around `pos` inside min..max, or centers it if
+/// the bounds are smaller than the view
+fn clamp_view(pos: f32, half_view: f32, min: f32, max: f32) -> f32 {
+    if max - min < half_view * 2. {
+        (min + max) / 2.
+    } else {
+        pos.max(min + half_view).min(max - half_view)
+    }
+}
+
+pub struct CameraSystem {
+    pub frametime: f32,
+    pub target: specs::Entity,
+}
+
+impl<'a> specs::System<'a> for CameraSystem {
+    type SystemData = (specs::ReadStorage<'a, Transform>, specs::FetchMut<'a, Camera>);
+    fn run(&mut self, (transforms, mut camera): Self::SystemData) {
+        if let Some(transform) = transforms.get(self.target) {
+            camera.follow(transform.pos, self.frametime);
+        }
+    }
+}
@@ -1,16 +1,18 @@
 extern crate specs;
 
-use nalgebra::{Vector2};
-
-use components::{Transform, BoundingCircle, BallType};
-use components::{HitBad, HitNeutral, HitGood};
+use components::{Transform, BoundingCircle, BallType, Lifetime};
+use components::{HitNeutral, HitGood};
+use cone::{ConeSize, ViewCone};
+use dash::Dash;
+use game::RespawnComponent;
+use health::{Damage, DamageEvents, Health};
+use player::PlayerComponent;
+use powerup::{ActiveEffects, Effect};
+use spawner::{BallEvent, BallEvents};
 
 use specs::Join;
 
-pub struct CollisionSystem {
-    pub player: specs::Entity,
-    pub new_points: i32,
-}
+pub struct CollisionSystem;
 
 fn are_colliding(tr1: &Transform, bb1: &BoundingCircle,
                  tr2: &Transform, bb2: &BoundingCircle) -> bool
@@ -25,33 +27,97 @@ fn are_colliding(tr1: &Transform, bb1: &BoundingCircle,
 
 impl<'a> specs::System<'a> for CollisionSystem {
     type SystemData = (
-        specs::WriteStorage<'a, Transform>,
+        specs::Entities<'a>,
+        specs::ReadStorage<'a, Transform>,
         specs::ReadStorage<'a, BoundingCircle>,
         specs::ReadStorage<'a, BallType>,
-        specs::FetchMut<'a, HitBad>,
+        specs::ReadStorage<'a, RespawnComponent>,
+        specs::ReadStorage<'a, Lifetime>,
+        specs::ReadStorage<'a, PlayerComponent>,
+        specs::ReadStorage<'a, Health>,
+        specs::ReadStorage<'a, Dash>,
         specs::FetchMut<'a, HitNeutral>,
         specs::FetchMut<'a, HitGood>,
+        specs::FetchMut<'a, BallEvents>,
+        specs::FetchMut<'a, DamageEvents>,
+        specs::FetchMut<'a, ActiveEffects>,
+        specs::Fetch<'a, ConeSize>,
     );
     fn run(&mut self, data: Self::SystemData) {
-        let (mut transforms, bounding_circles, ball_types, mut hit_bad, mut hit_neutral, mut hit_good) = data;
-        hit_bad.0 = false;
+        let (
+            entities,
+            transforms,
+            bounding_circles,
+            ball_types,
+            respawns,
+            lifetimes,
+            players,
+            healths,
+            dashes,
+            mut hit_neutral,
+            mut hit_good,
+            mut ball_events,
+            mut damage_events,
+            mut active_effects,
+            cone_size
+        ) = data;
         hit_neutral.0 = false;
         hit_good.0 = false;
 
-        self.new_points = 0;
+        // Deleted entities stay in the storages until the next maintain, so keep
+        // track of the ones that are already taken
+        let mut collected = vec!();
+
+        for (player, player_transform, player_circle, _, health) in
+            (&*entities, &transforms, &bounding_circles, &players, &healths).join()
+        {
+            if !health.is_alive() {
+                continue;
+            }
+
+            let player_cone = ViewCone::of_player(&player_transform, &cone_size);
+            let dashing_through = dashes.get(player).map(|dash| dash.is_invulnerable()).unwrap_or(false);
+
+            for (entity, transform, bounding_circle, ball_type) in (&*entities, &transforms, &bounding_circles, &ball_types).join() {
+                if collected.contains(&entity) {
+                    continue;
+                }
+
+                if are_colliding(&player_transform, &player_circle, &transform, &bounding_circle) {
+                    // Pooled balls are removed and replaced by the spawner later,
+                    // temporary ones are just removed
+                    let pooled = respawns.get(entity).is_some();
+                    let removed = pooled || lifetimes.get(entity).is_some();
 
-        let player_transform = transforms.get(self.player).unwrap().clone();
-        let player_circle = bounding_circles.get(self.player).unwrap();
+                    // A shield soaks up one bad ball, invulnerable players don't
+                    // use it up. Balls that stay in place would use it up again
+                    // on the next frame, so they don't use it at all
+                    let invulnerable = !health.can_be_hit() || dashing_through;
+                    let absorbed = *ball_type == BallType::Bad
+                        && (invulnerable || (removed && active_effects.consume(Effect::Shield)));
 
-        for (mut transform, bounding_circle, ball_type) in (&mut transforms, &bounding_circles, &ball_types).join() {
-            if are_colliding(&player_transform, &player_circle, &transform, &bounding_circle) {
-                //Respawn the ball
-                transform.pos = Vector2::new(5000., 5000.);
+                    if removed {
+                        entities.delete(entity).unwrap();
+                        collected.push(entity);
+                        ball_events.0.push(BallEvent::Collected {
+                            ball_type: *ball_type,
+                            pos: transform.pos,
+                            pooled,
+                            in_cone: player_cone.contains_entity(transform),
+                            absorbed,
+                            player,
+                        });
+                    }
 
-                match *ball_type {
-                    BallType::Good => { self.new_points += 1; hit_good.0 = true },
-                    BallType::Neutral => hit_neutral.0 = true,
-                    BallType::Bad => hit_bad.0 = true,
+                    match *ball_type {
+                        BallType::Good => hit_good.0 = true,
+                        BallType::Neutral => hit_neutral.0 = true,
+                        BallType::Bad => if !absorbed {
+                            damage_events.0.push(Damage { player, source: transform.pos });
+                        },
+                        // Power-ups take effect in the PowerUpSystem
+                        _ => {}
+                    }
                 }
             }
         }
@@ -4,13 +4,53 @@ extern crate nalgebra;
 use specs::{VecStorage, NullStorage};
 use nalgebra::Vector2;
 
-#[derive(Component, Debug, Copy, Clone)]
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[component(VecStorage)]
 pub enum BallType
 {
     Good,
     Neutral,
     Bad,
+    Heal,
+    Shield,
+    Magnet,
+    SlowTime,
+    ConeWidener,
+    Splitter,
+}
+
+impl BallType {
+    pub fn all() -> [BallType; 9] {
+        [
+            BallType::Good,
+            BallType::Neutral,
+            BallType::Bad,
+            BallType::Heal,
+            BallType::Shield,
+            BallType::Magnet,
+            BallType::SlowTime,
+            BallType::ConeWidener,
+            BallType::Splitter,
+        ]
+    }
+
+    pub fn from_name(name: &str) -> Option<BallType> {
+        BallType::all().iter().cloned().find(|ball_type| ball_type.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            BallType::Good => "good",
+            BallType::Neutral => "neutral",
+            BallType::Bad => "bad",
+            BallType::Heal => "heal",
+            BallType::Shield => "shield",
+            BallType::Magnet => "magnet",
+            BallType::SlowTime => "slow_time",
+            BallType::ConeWidener => "cone_widener",
+            BallType::Splitter => "splitter",
+        }
+    }
 }
 
 #[derive(Component, Default)]
@@ -59,13 +99,26 @@ impl Default for BoundingCircle {
     }
 }
 
+// Seconds until the entity is deleted
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+#[component(VecStorage)]
+pub struct Lifetime(pub f32);
+
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+#[component(VecStorage)]
+pub struct RigidBody {
+    pub mass: f32,
+    // 1 is a perfectly elastic bounce, 0 stops the body dead
+    pub restitution: f32,
+}
+
 pub struct StretchComponent
 {
     pub amount: f32,
     pub original: Vector2<f32>,
 }
 
-#[derive(Component)]
+#[derive(Component, Copy, Clone, Debug)]
 #[component(VecStorage)]
 pub struct OrbitComponent
 {
@@ -78,4 +131,7 @@ pub struct OrbitComponent
 pub struct HitBad(pub bool);
 pub struct HitNeutral(pub bool);
 pub struct HitGood(pub bool);
+// Trauma that a script wants the camera to shake with this frame
 pub struct ScreenShake(pub Option<f32>);
+// How fast everything except the players moves
+pub struct TimeScale(pub f32);
@@ -0,0 +1,83 @@
+use std::f64::consts;
+
+use nalgebra::Vector2;
+
+use components::Transform;
+
+/// What the colour cone points at
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConeMode {
+    // Towards the mouse cursor
+    Mouse,
+    // The way the player is moving, for playing with only the keyboard
+    Movement,
+}
+
+impl ConeMode {
+    pub fn all() -> [ConeMode; 2] {
+        [ConeMode::Mouse, ConeMode::Movement]
+    }
+
+    pub fn from_name(name: &str) -> Option<ConeMode> {
+        ConeMode::all().iter().cloned().find(|mode| mode.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ConeMode::Mouse => "mouse",
+            ConeMode::Movement => "movement",
+        }
+    }
+}
+
+// Width of the colour cone as a fraction of a full turn
+pub struct ConeSize {
+    pub base: f32,
+    pub multiplier: f32,
+}
+
+impl ConeSize {
+    pub fn get(&self) -> f32 {
+        self.base * self.multiplier
+    }
+}
+
+/// The area where a player sees true colours. Shared by rendering and gameplay so
+/// that what counts as "in the cone" is always what the player sees
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ViewCone {
+    pub origin: Vector2<f32>,
+    pub angle: f64,
+    // Fraction of a full turn on each side of `angle`
+    pub size: f64,
+}
+
+impl ViewCone {
+    pub fn of_player(player: &Transform, cone_size: &ConeSize) -> ViewCone {
+        ViewCone {
+            origin: player.pos,
+            angle: player.angle,
+            size: cone_size.get() as f64,
+        }
+    }
+
+    pub fn contains(&self, point: Vector2<f32>) -> bool {
+        let diff = point - self.origin;
+        let point_angle = (diff.y as f64).atan2(diff.x as f64);
+
+        // Wrap the difference into -pi..pi so that the cone works across the
+        // point where atan2 jumps from pi to -pi
+        let mut angle_diff = (self.angle - point_angle) % (consts::PI * 2.);
+        if angle_diff > consts::PI {
+            angle_diff -= consts::PI * 2.;
+        } else if angle_diff < -consts::PI {
+            angle_diff += consts::PI * 2.;
+        }
+
+        angle_diff.abs() < self.size * consts::PI * 2.
+    }
+
+    pub fn contains_entity(&self, transform: &Transform) -> bool {
+        self.contains(transform.pos)
+    }
+}
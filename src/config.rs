@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// One `[name]` block of a data file along with its `key = value` pairs
+#[derive(Clone, Debug)]
+pub struct Section {
+    pub name: String,
+    values: HashMap<String, String>,
+}
+
+impl Section {
+    pub fn new(name: &str) -> Section {
+        Section {
+            name: name.into(),
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|value| value.as_str())
+    }
+
+    pub fn get<T: FromStr>(&self, key: &str) -> Result<Option<T>, String> {
+        match self.values.get(key) {
+            Some(value) => value.parse::<T>()
+                .map(Some)
+                .map_err(|_| format!("[{}] invalid value for {}: {}", self.name, key, value)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_or<T: FromStr>(&self, key: &str, default: T) -> Result<T, String> {
+        self.get(key).map(|value| value.unwrap_or(default))
+    }
+
+    pub fn set<T: ToString>(&mut self, key: &str, value: T) {
+        self.values.insert(key.into(), value.to_string());
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.values.remove(key)
+    }
+}
+
+/// Parses the ini-like format used by the files in `data`: `[section]` headers
+/// followed by `key = value` lines, with `#` starting a comment. Sections may be
+/// repeated and are returned in file order.
+pub fn parse(text: &str) -> Result<Vec<Section>, String> {
+    let mut sections = vec!();
+
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            sections.push(Section::new(line[1..line.len() - 1].trim()));
+        } else if let Some(separator) = line.find('=') {
+            let key = line[..separator].trim();
+            let value = line[separator + 1..].trim();
+            match sections.last_mut() {
+                Some(section) => section.set(key, value),
+                None => return Err(format!("line {}: value outside of a section", line_number + 1)),
+            }
+        } else {
+            return Err(format!("line {}: expected [section] or key = value", line_number + 1));
+        }
+    }
+
+    Ok(sections)
+}
+
+pub fn load(path: &str) -> Result<Vec<Section>, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|e| format!("{}: {}", path, e))?;
+
+    parse(&text).map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Turns sections back into text that `parse` can read. Keys are written in
+/// alphabetical order
+pub fn format(sections: &[Section]) -> String {
+    let mut text = String::new();
+    for section in sections {
+        text.push_str(&format!("[{}]\n", section.name));
+
+        let mut keys = section.values.keys().collect::<Vec<_>>();
+        keys.sort();
+        for key in keys {
+            text.push_str(&format!("{} = {}\n", key, section.values[key]));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Writes `sections` to `path` after `header` comment lines. The file is written
+/// next to its destination and renamed over it, so a crash halfway through
+/// leaves the old file intact
+pub fn save(path: &str, header: &str, sections: &[Section]) -> Result<(), String> {
+    let temp_path = format!("{}.tmp", path);
+    let text = header.lines().map(|line| format!("# {}\n", line)).collect::<String>()
+        + "\n"
+        + &format(sections);
+
+    File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(text.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp_path, path))
+        .map_err(|e| format!("{}: {}", path, e))
+}
@@ -3,4 +3,5 @@
 //              Global variables
 /////////////////////////////////////////////////
 pub const RESOLUTION: (u32, u32) = (350, 350);
-pub const UPSCALING: u32 = 2;
+// Size of the area the game takes place in. The camera shows RESOLUTION of it
+pub const WORLD_SIZE: (u32, u32) = (500, 500);
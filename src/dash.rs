@@ -0,0 +1,170 @@
+extern crate specs;
+
+use nalgebra::{Vector2, Norm};
+
+use specs::{Join, VecStorage};
+
+use audio::SoundQueue;
+use components::{Transform, Velocity, MaxVelocity, Lifetime};
+use health::Health;
+use player::{self, PlayerComponent};
+use sprite::Sprite;
+
+const DASH_SPEED: f32 = 350.;
+// Seconds that a dash lasts
+const DASH_TIME: f32 = 0.2;
+// Seconds after a dash ends before the next one can start
+const DASH_COOLDOWN: f32 = 0.5;
+const MAX_STAMINA: f32 = 100.;
+const DASH_COST: f32 = 40.;
+// Stamina regained per second while not dashing
+const STAMINA_REGEN: f32 = 25.;
+// Seconds between the ghost images left behind while dashing
+const TRAIL_INTERVAL: f32 = 0.03;
+const TRAIL_LIFETIME: f32 = 0.25;
+
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+#[component(VecStorage)]
+pub struct Dash {
+    pub stamina: f32,
+    // Seconds left of the current dash
+    pub active: f32,
+    // Seconds left until the player may dash again
+    pub cooldown: f32,
+    // Whether dashing players can pass through bad balls unharmed
+    pub iframes: bool,
+    // Speed cap to go back to once the dash ends
+    normal_max_velocity: f32,
+    // Dashes start when the key goes down, not while it is held
+    key_held: bool,
+    next_trail: f32,
+}
+
+impl Dash {
+    pub fn new(iframes: bool) -> Dash {
+        Dash {
+            stamina: MAX_STAMINA,
+            active: 0.,
+            cooldown: 0.,
+            iframes,
+            normal_max_velocity: 0.,
+            key_held: false,
+            next_trail: 0.,
+        }
+    }
+
+    pub fn is_dashing(&self) -> bool {
+        self.active > 0.
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.iframes && self.is_dashing()
+    }
+
+    pub fn can_dash(&self) -> bool {
+        !self.is_dashing() && self.cooldown <= 0. && self.stamina >= DASH_COST
+    }
+
+    /// Stamina as a fraction between 0 and 1, for the stamina bar
+    pub fn stamina_fraction(&self) -> f32 {
+        self.stamina / MAX_STAMINA
+    }
+}
+
+/// Starts dashes when the dash key is pressed, and ends them again. Runs after
+/// the input system and before the `MaxVelSystem`
+pub struct DashSystem {
+    pub frametime: f32,
+    // Sprite of the ghost images that make up the trail
+    pub trail_sprite: Sprite,
+}
+
+impl<'a> specs::System<'a> for DashSystem {
+    type SystemData = (
+        specs::Entities<'a>,
+        specs::WriteStorage<'a, Dash>,
+        specs::WriteStorage<'a, Velocity>,
+        specs::WriteStorage<'a, MaxVelocity>,
+        specs::WriteStorage<'a, Transform>,
+        specs::WriteStorage<'a, Sprite>,
+        specs::WriteStorage<'a, Lifetime>,
+        specs::ReadStorage<'a, PlayerComponent>,
+        specs::ReadStorage<'a, Health>,
+        specs::FetchMut<'a, SoundQueue>,
+    );
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut dashes,
+            mut velocities,
+            mut max_velocities,
+            mut transforms,
+            mut sprites,
+            mut lifetimes,
+            players,
+            healths,
+            mut sound_queue
+        ) = data;
+
+        let mut trails = vec!();
+
+        for (entity, dash, velocity, max_velocity, player) in
+            (&*entities, &mut dashes, &mut velocities, &mut max_velocities, &players).join()
+        {
+            let pressed = player.get_key(player::Keys::Dash);
+            let just_pressed = pressed && !dash.key_held;
+            dash.key_held = pressed;
+
+            let alive = healths.get(entity).map(|health| health.is_alive()).unwrap_or(true);
+            let transform = match transforms.get(entity) {
+                Some(transform) => *transform,
+                None => continue,
+            };
+
+            if dash.is_dashing() {
+                dash.active -= self.frametime;
+                dash.next_trail -= self.frametime;
+
+                if dash.next_trail <= 0. && alive {
+                    trails.push(transform);
+                    dash.next_trail = TRAIL_INTERVAL;
+                }
+
+                if !dash.is_dashing() {
+                    dash.active = 0.;
+                    dash.cooldown = DASH_COOLDOWN;
+                    max_velocity.0 = dash.normal_max_velocity;
+                }
+                continue;
+            }
+
+            dash.cooldown = (dash.cooldown - self.frametime).max(0.);
+            dash.stamina = (dash.stamina + STAMINA_REGEN * self.frametime).min(MAX_STAMINA);
+
+            if just_pressed && alive && dash.can_dash() {
+                // Dash the way the player is moving, or where they are looking
+                // if they are standing still
+                let direction = if velocity.0.norm_squared() > 0. {
+                    velocity.0.normalize()
+                } else {
+                    Vector2::new(transform.angle.cos() as f32, transform.angle.sin() as f32)
+                };
+
+                dash.stamina -= DASH_COST;
+                dash.active = DASH_TIME;
+                dash.next_trail = 0.;
+                dash.normal_max_velocity = max_velocity.0;
+                max_velocity.0 = DASH_SPEED;
+                velocity.0 = direction * DASH_SPEED;
+                sound_queue.0.push("dash".to_string());
+            }
+        }
+
+        for transform in trails {
+            let trail = entities.create();
+            transforms.insert(trail, transform);
+            sprites.insert(trail, self.trail_sprite);
+            lifetimes.insert(trail, Lifetime(TRAIL_LIFETIME));
+        }
+    }
+}
@@ -0,0 +1,275 @@
+extern crate specs;
+extern crate rand;
+
+use rand::Rng;
+
+use specs::{Join, World};
+
+use assets::Assets;
+use audio::SoundQueue;
+use bounds::{EdgeMode, PlayerBounds};
+use components::BallType;
+use cone::ConeSize;
+use config::Section;
+use game::RespawnComponent;
+
+// How often the director looks at how well the player is doing
+const EVALUATION_PERIOD: f32 = 10.;
+const MIN_DIFFICULTY: f32 = 0.5;
+const MAX_DIFFICULTY: f32 = 2.0;
+
+/// Something out of the ordinary that happens when a wave starts
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WaveEvent {
+    BallBurst(u32),
+    ObamaSwarm(u32),
+}
+
+impl WaveEvent {
+    /// Parses events like `ball_burst 5`
+    fn parse(text: &str) -> Result<WaveEvent, String> {
+        let mut words = text.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let amount = words.next().unwrap_or("1").parse::<u32>()
+            .map_err(|_| format!("invalid amount in wave event: {}", text))?;
+
+        match name {
+            "ball_burst" => Ok(WaveEvent::BallBurst(amount)),
+            "obama_swarm" => Ok(WaveEvent::ObamaSwarm(amount)),
+            _ => Err(format!("unknown wave event: {}", text)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Wave {
+    pub name: String,
+    pub duration: f32,
+    pub spawn_time: f32,
+    pub ball_weights: Vec<(BallType, f32)>,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    pub cone_size: f32,
+    // Points per second that a player who is keeping up is expected to score
+    pub target_score_rate: f32,
+    // What the player runs into at the edge of the world
+    pub edges: EdgeMode,
+    pub events: Vec<WaveEvent>,
+}
+
+impl Wave {
+    fn from_section(section: &Section, default_weights: &[(BallType, f32)]) -> Result<Wave, String> {
+        let name = section.get_str("name").unwrap_or("unnamed").to_string();
+
+        let mut ball_weights = vec!();
+        for &(ball_type, default_weight) in default_weights {
+            let weight = section.get_or(ball_type.name(), default_weight)?;
+            if weight > 0. {
+                ball_weights.push((ball_type, weight));
+            }
+        }
+        if ball_weights.is_empty() {
+            return Err(format!("wave {} has no ball weights", name));
+        }
+
+        let events = match section.get_str("events") {
+            Some(events) => events.split(',')
+                .map(|event| WaveEvent::parse(event.trim()))
+                .collect::<Result<Vec<_>, _>>()?,
+            None => vec!(),
+        };
+
+        let edges = match section.get_str("edges") {
+            Some(edges) => EdgeMode::from_name(edges)
+                .ok_or_else(|| format!("wave {} has unknown edges {}", name, edges))?,
+            None => EdgeMode::Clamp,
+        };
+
+        Ok(Wave {
+            duration: section.get_or("duration", 60.)?,
+            spawn_time: section.get_or("spawn_time", 3.)?,
+            ball_weights,
+            min_speed: section.get_or("min_speed", 70.)?,
+            max_speed: section.get_or("max_speed", 120.)?,
+            cone_size: section.get_or("cone_size", 0.07)?,
+            target_score_rate: section.get_or("target_score_rate", 0.5)?,
+            edges,
+            events,
+            name,
+        })
+    }
+}
+
+/// Loads the waves from a file. Ball types that a wave doesn't mention use their
+/// default weight
+pub fn load_waves(path: &str, default_weights: &[(BallType, f32)], assets: &Assets) -> Result<Vec<Wave>, String> {
+    let waves = assets.load_config(path)?.iter()
+        .filter(|section| section.name == "wave")
+        .map(|section| Wave::from_section(section, default_weights))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("{}: {}", path, e))?;
+
+    if waves.is_empty() {
+        return Err(format!("{}: no waves defined", path));
+    }
+    Ok(waves)
+}
+
+/// Resource with the ball spawning parameters of the current wave, adjusted for
+/// the current difficulty
+pub struct SpawnRules {
+    pub spawn_time: f32,
+    pub ball_weights: Vec<(BallType, f32)>,
+    pub min_speed: f32,
+    pub max_speed: f32,
+}
+
+impl SpawnRules {
+    pub fn choose_ball_type<R: Rng>(&self, rng: &mut R) -> BallType {
+        let total = self.ball_weights.iter().map(|&(_, weight)| weight).sum::<f32>();
+        let mut choice = rng.gen_range(0., total);
+
+        for &(ball_type, weight) in &self.ball_weights {
+            if choice < weight {
+                return ball_type;
+            }
+            choice -= weight;
+        }
+        self.ball_weights.last().unwrap().0
+    }
+}
+
+impl Default for SpawnRules {
+    fn default() -> SpawnRules {
+        SpawnRules {
+            spawn_time: 3.,
+            ball_weights: vec!(
+                (BallType::Good, 1.),
+                (BallType::Neutral, 1.),
+                (BallType::Bad, 1.),
+            ),
+            min_speed: 70.,
+            max_speed: 120.,
+        }
+    }
+}
+
+/// Steps through the waves and scales their difficulty depending on how well the
+/// player is doing. Players who keep up with the expected score rate get faster
+/// and more frequent balls, players who lose lives get some breathing room.
+pub struct Director {
+    waves: Vec<Wave>,
+    current_wave: Option<usize>,
+    wave_start: f32,
+
+    pub difficulty: f32,
+    last_evaluation: f32,
+    points_at_evaluation: i32,
+    life_at_evaluation: i32,
+}
+
+impl Director {
+    pub fn new(waves: Vec<Wave>) -> Director {
+        Director {
+            waves,
+            current_wave: None,
+            wave_start: 0.,
+
+            difficulty: 1.,
+            last_evaluation: 0.,
+            points_at_evaluation: 0,
+            life_at_evaluation: 0,
+        }
+    }
+
+    /// Swaps in waves that were loaded again. The wave at the same position in
+    /// the list takes over from the current one
+    pub fn set_waves(&mut self, waves: Vec<Wave>) {
+        self.current_wave = self.current_wave.map(|index| index.min(waves.len().saturating_sub(1)));
+        self.waves = waves;
+    }
+
+    pub fn current_wave(&self) -> Option<&Wave> {
+        self.current_wave.map(|index| &self.waves[index])
+    }
+
+    /// Advances the waves and applies their rules to the world. Returns the events
+    /// of any wave that started this frame
+    pub fn update(&mut self, world: &mut World, curr_time: f32, points: i32, life: i32) -> Vec<WaveEvent> {
+        let next_wave = match self.current_wave {
+            None => {
+                self.last_evaluation = curr_time;
+                self.points_at_evaluation = points;
+                self.life_at_evaluation = life;
+                Some(0)
+            }
+            Some(index) if index + 1 < self.waves.len()
+                && curr_time - self.wave_start > self.waves[index].duration => Some(index + 1),
+            _ => None,
+        };
+
+        let mut events = vec!();
+        let mut rules_changed = false;
+
+        if let Some(index) = next_wave {
+            self.current_wave = Some(index);
+            self.wave_start = curr_time;
+            events = self.waves[index].events.clone();
+            rules_changed = true;
+            world.write_resource::<SoundQueue>().0.push("wave_start".to_string());
+        }
+
+        if curr_time - self.last_evaluation > EVALUATION_PERIOD {
+            rules_changed |= self.evaluate_player(curr_time, points, life);
+        }
+
+        if rules_changed {
+            self.apply_rules(world);
+        }
+
+        events
+    }
+
+    fn evaluate_player(&mut self, curr_time: f32, points: i32, life: i32) -> bool {
+        let target_rate = self.current_wave().unwrap().target_score_rate;
+        let score_rate = (points - self.points_at_evaluation) as f32 / (curr_time - self.last_evaluation);
+        let lives_lost = self.life_at_evaluation - life;
+
+        let old_difficulty = self.difficulty;
+        if lives_lost > 0 {
+            self.difficulty -= 0.15 * lives_lost as f32;
+        } else if score_rate >= target_rate {
+            self.difficulty += 0.1;
+        } else if score_rate < target_rate / 2. {
+            self.difficulty -= 0.05;
+        }
+        self.difficulty = self.difficulty.max(MIN_DIFFICULTY).min(MAX_DIFFICULTY);
+
+        self.last_evaluation = curr_time;
+        self.points_at_evaluation = points;
+        self.life_at_evaluation = life;
+
+        self.difficulty != old_difficulty
+    }
+
+    fn apply_rules(&self, world: &mut World) {
+        let wave = self.current_wave().unwrap();
+
+        let rules = SpawnRules {
+            spawn_time: wave.spawn_time / self.difficulty,
+            ball_weights: wave.ball_weights.clone(),
+            min_speed: wave.min_speed * self.difficulty,
+            max_speed: wave.max_speed * self.difficulty,
+        };
+
+        // Balls that are already out there use the new speeds the next time they respawn
+        for respawn in (&mut world.write::<RespawnComponent>()).join() {
+            respawn.min_speed = rules.min_speed;
+            respawn.max_speed = rules.max_speed;
+        }
+
+        *world.write_resource::<SpawnRules>() = rules;
+        world.write_resource::<ConeSize>().base = wave.cone_size;
+        *world.write_resource::<PlayerBounds>() = PlayerBounds(wave.edges);
+    }
+}
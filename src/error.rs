@@ -0,0 +1,56 @@
+use std::fmt;
+
+/// Errors that stop the game. Everything below `main` reports errors as
+/// strings, they get sorted into these when they reach it
+#[derive(Debug)]
+pub enum GameError {
+    // SDL or one of its parts could not be started
+    Startup { what: &'static str, message: String },
+    // A file that the game needs is missing or can't be read
+    Asset { path: String, message: String },
+    Audio(String),
+    Rendering(String),
+}
+
+impl GameError {
+    /// For `map_err` on the result of starting `what`
+    pub fn startup<E: fmt::Display>(what: &'static str) -> impl Fn(E) -> GameError {
+        move |e| GameError::Startup { what, message: e.to_string() }
+    }
+
+    /// For `map_err` on the result of loading `path`
+    pub fn asset<E: fmt::Display>(path: &str) -> impl Fn(E) -> GameError {
+        let path = path.to_string();
+        move |e| {
+            // Loaders tend to start their errors with the path as well
+            let message = e.to_string();
+            let prefix = format!("{}: ", path);
+            let message = if message.starts_with(&prefix) { message[prefix.len()..].to_string() } else { message };
+            GameError::Asset { path: path.clone(), message }
+        }
+    }
+
+    pub fn rendering<E: fmt::Display>(e: E) -> GameError {
+        GameError::Rendering(e.to_string())
+    }
+
+    /// What the player can do about it, if anything
+    pub fn hint(&self) -> Option<&'static str> {
+        match *self {
+            GameError::Startup { .. } => Some("Check that your graphics drivers are installed."),
+            GameError::Asset { .. } => Some("The game may be missing files, try installing it again."),
+            GameError::Audio(_) | GameError::Rendering(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameError::Startup { what, ref message } => write!(f, "Could not start {}: {}", what, message),
+            GameError::Asset { ref path, ref message } => write!(f, "Could not load {}: {}", path, message),
+            GameError::Audio(ref message) => write!(f, "Audio error: {}", message),
+            GameError::Rendering(ref message) => write!(f, "Rendering error: {}", message),
+        }
+    }
+}
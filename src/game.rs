@@ -14,7 +14,8 @@ use specs::{Join, VecStorage};
 use sprite::Sprite;
 use constants::*;
 
-use components::{Transform, Velocity, MaxVelocity, ObamaComponent, OrbitComponent};
+use components::{Transform, Velocity, MaxVelocity, ObamaComponent, OrbitComponent, Lifetime, TimeScale};
+use player::PlayerComponent;
 
 #[derive(Component, Copy, Clone, Debug, PartialEq)]
 #[component(VecStorage)]
@@ -30,11 +31,36 @@ pub struct MotionSystem {
 }
 
 impl<'a> specs::System<'a> for MotionSystem {
-    type SystemData = (specs::WriteStorage<'a, Transform>, specs::ReadStorage<'a, Velocity>);
-    fn run(&mut self, (mut transforms, velocities): Self::SystemData) {
-        for (transform, &Velocity(vel)) in (&mut transforms, &velocities).join() {
+    type SystemData = (
+        specs::WriteStorage<'a, Transform>,
+        specs::ReadStorage<'a, Velocity>,
+        specs::ReadStorage<'a, PlayerComponent>,
+        specs::Fetch<'a, TimeScale>,
+    );
+    fn run(&mut self, (mut transforms, velocities, players, time_scale): Self::SystemData) {
+        for (transform, &Velocity(vel), _) in (&mut transforms, &velocities, &players).join() {
             transform.pos += vel * self.frametime;
         }
+        // Everything else is affected by slow motion
+        for (transform, &Velocity(vel), _) in (&mut transforms, &velocities, !&players).join() {
+            transform.pos += vel * self.frametime * time_scale.0;
+        }
+    }
+}
+
+pub struct LifetimeSystem {
+    pub frametime: f32,
+}
+
+impl<'a> specs::System<'a> for LifetimeSystem {
+    type SystemData = (specs::Entities<'a>, specs::WriteStorage<'a, Lifetime>);
+    fn run(&mut self, (entities, mut lifetimes): Self::SystemData) {
+        for (entity, lifetime) in (&*entities, &mut lifetimes).join() {
+            lifetime.0 -= self.frametime;
+            if lifetime.0 <= 0. {
+                entities.delete(entity).unwrap();
+            }
+        }
     }
 }
 
@@ -42,9 +68,9 @@ pub struct ObamaSystem {
     pub too_few_obamas: bool
 }
 
-fn way_off_screen(pos: Vector2<f32>) -> bool {
-    (pos.x as i32) < -100 || pos.x as u32 > RESOLUTION.0 + 100 ||
-        (pos.y as i32) < -100 || pos.y as u32 > RESOLUTION.1 + 100
+fn way_outside_world(pos: Vector2<f32>) -> bool {
+    (pos.x as i32) < -100 || pos.x as u32 > WORLD_SIZE.0 + 100 ||
+        (pos.y as i32) < -100 || pos.y as u32 > WORLD_SIZE.1 + 100
 }
 
 impl<'a> specs::System<'a> for ObamaSystem {
@@ -60,7 +86,7 @@ impl<'a> specs::System<'a> for ObamaSystem {
             obama_amount += 1;
 
             // Remove obamas that are too far out
-            if way_off_screen(transform.pos) {
+            if way_outside_world(transform.pos) {
                 entities.delete(entity).unwrap();
             }
         }
@@ -93,7 +119,7 @@ impl<'a> specs::System<'a> for RespawnSystem
         specs::ReadStorage<'a, RespawnComponent>,
     );
     fn run(&mut self, (mut transforms, mut velocities, respawns): Self::SystemData) {
-        let center = Vector2::new((RESOLUTION.0 / 2) as f32, (RESOLUTION.1 / 2) as f32);
+        let center = Vector2::new((WORLD_SIZE.0 / 2) as f32, (WORLD_SIZE.1 / 2) as f32);
         let mut rng = rand::thread_rng();
 
         for (transform, velocity, respawn) in (&mut transforms, &mut velocities, &respawns).join() {
@@ -122,7 +148,6 @@ impl<'a> specs::System<'a> for RespawnSystem
 pub struct OrbitSystem
 {
     pub player: specs::Entity,
-    pub nuke_angle: f32,
 }
 
 impl<'a> specs::System<'a> for OrbitSystem
@@ -148,8 +173,6 @@ impl<'a> specs::System<'a> for OrbitSystem
 
             transform.pos = pos;
             transform.angle = orbit.angle as f64 - consts::PI * 0.5; 
-
-            self.nuke_angle = orbit.angle;
         }
     }
 }
@@ -157,21 +180,21 @@ impl<'a> specs::System<'a> for OrbitSystem
 fn random_edge_position() -> Vector2<f32>
 {
     // Distance to corners going clockwise
-    let c1 = RESOLUTION.0; // top left
-    let c2 = c1 + RESOLUTION.1; // top right
-    let c3 = c2 + RESOLUTION.0; // bottom right
+    let c1 = WORLD_SIZE.0; // top left
+    let c2 = c1 + WORLD_SIZE.1; // top right
+    let c3 = c2 + WORLD_SIZE.0; // bottom right
 
     let mut rng = rand::thread_rng();
-    let between_corners = Range::new(0, RESOLUTION.0*2 + RESOLUTION.1*2);
+    let between_corners = Range::new(0, WORLD_SIZE.0*2 + WORLD_SIZE.1*2);
     let rand_dist = between_corners.ind_sample(&mut rng);
     let edge_offset = 50.0;
 
     if rand_dist < c1 {
         Vector2::new(rand_dist as f32, -edge_offset)
     } else if rand_dist < c2 {
-        Vector2::new(RESOLUTION.0 as f32 + edge_offset, (rand_dist - c1) as f32)
+        Vector2::new(WORLD_SIZE.0 as f32 + edge_offset, (rand_dist - c1) as f32)
     } else if rand_dist < c3 {
-        Vector2::new((rand_dist - c2) as f32, RESOLUTION.1 as f32 + edge_offset)
+        Vector2::new((rand_dist - c2) as f32, WORLD_SIZE.1 as f32 + edge_offset)
     } else {
         Vector2::new(-edge_offset, (rand_dist - c3) as f32)
     }
@@ -0,0 +1,167 @@
+extern crate specs;
+
+use nalgebra::{Vector2, Norm};
+
+use specs::{Entity, Join, VecStorage, World};
+
+use audio::SoundQueue;
+use components::{Transform, Velocity, HitBad};
+use sprite::Sprite;
+
+// Seconds of invulnerability after being hit or respawning
+const INVULNERABILITY_TIME: f32 = 2.;
+const BLINKS_PER_SECOND: f32 = 10.;
+const KNOCKBACK_SPEED: f32 = 150.;
+// Seconds between running out of lives and respawning or the game ending
+const RESPAWN_DELAY: f32 = 2.;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LifeState {
+    Alive,
+    Dead { respawn_in: f32 },
+}
+
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+#[component(VecStorage)]
+pub struct Health {
+    // Extra hits the player can take. Going below zero kills the player
+    pub lives: i32,
+    // Seconds of invulnerability left
+    pub invulnerable: f32,
+    pub state: LifeState,
+    pub spawn_point: Vector2<f32>,
+}
+
+impl Health {
+    pub fn new(lives: i32, spawn_point: Vector2<f32>) -> Health {
+        Health {
+            lives,
+            invulnerable: 0.,
+            state: LifeState::Alive,
+            spawn_point,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.state == LifeState::Alive
+    }
+
+    pub fn can_be_hit(&self) -> bool {
+        self.is_alive() && self.invulnerable <= 0.
+    }
+}
+
+/// A player touched something harmful at `source`
+#[derive(Copy, Clone, Debug)]
+pub struct Damage {
+    pub player: Entity,
+    pub source: Vector2<f32>,
+}
+
+/// Damage dealt this frame, consumed by the `HealthSystem`
+pub struct DamageEvents(pub Vec<Damage>);
+
+/// Lives of all players together, for the HUD and the director
+pub fn total_lives(world: &World) -> i32 {
+    world.read::<Health>().join()
+        .map(|health| health.lives.max(0))
+        .sum()
+}
+
+/// The game is over once every player is dead and done with the death sequence
+pub fn all_players_gone(world: &World) -> bool {
+    world.read::<Health>().join().all(|health| match health.state {
+        LifeState::Dead { respawn_in } => respawn_in <= 0.,
+        LifeState::Alive => false,
+    })
+}
+
+/// Applies damage, knockback and invulnerability, and runs the death and respawn
+/// sequence of players
+pub struct HealthSystem {
+    pub frametime: f32,
+}
+
+impl<'a> specs::System<'a> for HealthSystem {
+    type SystemData = (
+        specs::WriteStorage<'a, Health>,
+        specs::WriteStorage<'a, Transform>,
+        specs::WriteStorage<'a, Velocity>,
+        specs::WriteStorage<'a, Sprite>,
+        specs::FetchMut<'a, DamageEvents>,
+        specs::FetchMut<'a, HitBad>,
+        specs::FetchMut<'a, SoundQueue>,
+    );
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut healths,
+            mut transforms,
+            mut velocities,
+            mut sprites,
+            mut damage_events,
+            mut hit_bad,
+            mut sound_queue
+        ) = data;
+
+        hit_bad.0 = false;
+        for damage in damage_events.0.drain(..) {
+            let (health, transform, velocity) = match (
+                healths.get_mut(damage.player),
+                transforms.get(damage.player),
+                velocities.get_mut(damage.player),
+            ) {
+                (Some(health), Some(transform), Some(velocity)) => (health, transform, velocity),
+                _ => continue,
+            };
+
+            if !health.can_be_hit() {
+                continue;
+            }
+
+            hit_bad.0 = true;
+            sound_queue.0.push("hurt".to_string());
+            health.lives -= 1;
+            health.invulnerable = INVULNERABILITY_TIME;
+
+            let away = transform.pos - damage.source;
+            if away.norm_squared() > 0. {
+                velocity.0 = away.normalize() * KNOCKBACK_SPEED;
+            }
+
+            if health.lives < 0 {
+                health.state = LifeState::Dead { respawn_in: RESPAWN_DELAY };
+            }
+        }
+
+        // Dead players come back if someone else is still in the game to cover them
+        let anyone_alive = (&healths).join().any(|health| health.is_alive());
+
+        for (health, transform, velocity, sprite) in
+            (&mut healths, &mut transforms, &mut velocities, &mut sprites).join()
+        {
+            health.invulnerable = (health.invulnerable - self.frametime).max(0.);
+
+            match health.state {
+                LifeState::Alive => {
+                    // Blink while invulnerable
+                    let blink = (health.invulnerable * BLINKS_PER_SECOND * 2.) as i32;
+                    sprite.visible = health.invulnerable <= 0. || blink % 2 == 0;
+                }
+                LifeState::Dead { respawn_in } => {
+                    sprite.visible = false;
+                    velocity.0 = Vector2::new(0., 0.);
+
+                    let respawn_in = respawn_in - self.frametime;
+                    if respawn_in <= 0. && anyone_alive {
+                        health.lives = 0;
+                        health.invulnerable = INVULNERABILITY_TIME;
+                        health.state = LifeState::Alive;
+                        transform.pos = health.spawn_point;
+                    } else {
+                        health.state = LifeState::Dead { respawn_in: respawn_in.max(0.) };
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+extern crate time;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use config::{self, Section};
+use paths;
+
+// Version of the high score file. Files with another version are not read
+const FILE_VERSION: u32 = 1;
+const FILE_NAME: &str = "highscores.txt";
+// Number of scores that are kept
+pub const TABLE_SIZE: usize = 10;
+pub const MAX_NAME_LENGTH: usize = 12;
+const DEFAULT_NAME: &str = "Anonymous";
+
+/// Removes characters that can't be shown or stored from a player name
+pub fn clean_name(name: &str) -> String {
+    let name = name.chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NAME_LENGTH)
+        .collect::<String>();
+    let name = name.trim();
+    if name.is_empty() { DEFAULT_NAME.to_string() } else { name.to_string() }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HighScore {
+    pub name: String,
+    pub points: i32,
+    // Seconds since the unix epoch
+    pub date: i64,
+    // Random number identifying the run. The game doesn't use seeded randomness
+    // yet, so runs can't be replayed from it
+    pub seed: u32,
+    // Seconds that the run lasted
+    pub duration: f32,
+    pub max_combo: u32,
+}
+
+impl HighScore {
+    pub fn from_section(section: &Section) -> Result<HighScore, String> {
+        Ok(HighScore {
+            name: clean_name(section.get_str("name").unwrap_or("")),
+            points: section.get("points")?.ok_or("score without points")?,
+            date: section.get_or("date", 0)?,
+            seed: section.get_or("seed", 0)?,
+            duration: section.get_or("duration", 0.)?,
+            max_combo: section.get_or("max_combo", 0)?,
+        })
+    }
+
+    pub fn to_section(&self) -> Section {
+        let mut section = Section::new("score");
+        section.set("name", &self.name);
+        section.set("points", self.points);
+        section.set("date", self.date);
+        section.set("seed", self.seed);
+        section.set("duration", self.duration);
+        section.set("max_combo", self.max_combo);
+        section
+    }
+
+    /// The date as `YYYY-MM-DD`
+    pub fn date_string(&self) -> String {
+        let date = time::at_utc(time::Timespec::new(self.date, 0));
+        time::strftime("%Y-%m-%d", &date).unwrap_or_default()
+    }
+
+    /// The duration as `m:ss`
+    pub fn duration_string(&self) -> String {
+        let seconds = self.duration.max(0.) as u32;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// Seconds since the unix epoch, for `HighScore::date`
+pub fn now() -> i64 {
+    time::get_time().sec
+}
+
+fn parse_table(sections: &[Section]) -> Result<Vec<HighScore>, String> {
+    let version = sections.iter()
+        .find(|section| section.name == "highscores")
+        .ok_or("missing [highscores] section")?
+        .get::<u32>("version")?
+        .ok_or("missing version")?;
+    if version != FILE_VERSION {
+        return Err(format!("unsupported version {}", version));
+    }
+
+    sections.iter()
+        .filter(|section| section.name == "score")
+        .map(HighScore::from_section)
+        .collect()
+}
+
+/// The best scores, highest first, along with the file they are stored in
+pub struct HighScoreTable {
+    pub scores: Vec<HighScore>,
+    path: Option<PathBuf>,
+}
+
+impl HighScoreTable {
+    /// A table that is never saved, for when there is nowhere to save it
+    pub fn in_memory() -> HighScoreTable {
+        HighScoreTable { scores: vec!(), path: None }
+    }
+
+    /// Loads the table from the data directory. A file that can't be read is
+    /// moved out of the way and the backup from the last save is used instead,
+    /// so a broken file never stops the game from starting
+    pub fn load() -> HighScoreTable {
+        let path = match paths::data_dir() {
+            Ok(dir) => dir.join(FILE_NAME),
+            Err(e) => {
+                println!("High scores will not be saved: {}", e);
+                return HighScoreTable::in_memory();
+            }
+        };
+        let backup_path = path.with_extension("txt.bak");
+
+        let mut table = HighScoreTable { scores: vec!(), path: Some(path.clone()) };
+        if !path.exists() {
+            return table;
+        }
+
+        match HighScoreTable::read(&path) {
+            Ok(scores) => table.scores = scores,
+            Err(e) => {
+                let corrupt_path = path.with_extension("txt.corrupt");
+                println!("Could not read high scores, moving them to {}: {}", corrupt_path.display(), e);
+                if let Err(e) = fs::rename(&path, &corrupt_path) {
+                    println!("Could not move {}: {}", path.display(), e);
+                }
+
+                if backup_path.exists() {
+                    match HighScoreTable::read(&backup_path) {
+                        Ok(scores) => table.scores = scores,
+                        Err(e) => println!("Could not read the high score backup either: {}", e),
+                    }
+                }
+            }
+        }
+
+        table.sort();
+        table
+    }
+
+    fn read(path: &Path) -> Result<Vec<HighScore>, String> {
+        let path = path.to_string_lossy();
+        let sections = config::load(&path)?;
+        parse_table(&sections).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    fn sort(&mut self) {
+        self.scores.sort_by(|a, b| b.points.cmp(&a.points).then(a.date.cmp(&b.date)));
+        self.scores.truncate(TABLE_SIZE);
+    }
+
+    /// The place that a score of `points` would get in the table, if any
+    pub fn rank_of(&self, points: i32) -> Option<usize> {
+        let rank = self.scores.iter().filter(|score| score.points >= points).count();
+        if rank < TABLE_SIZE { Some(rank) } else { None }
+    }
+
+    /// Adds a score to the table and returns its place, or None if it wasn't
+    /// good enough
+    pub fn insert(&mut self, score: HighScore) -> Option<usize> {
+        let rank = self.rank_of(score.points)?;
+        self.scores.insert(rank, score);
+        self.scores.truncate(TABLE_SIZE);
+        Some(rank)
+    }
+
+    /// Writes the table to its file. The previous file is kept as a backup in
+    /// case the new one gets damaged
+    pub fn save(&self) -> Result<(), String> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+
+        if path.exists() {
+            let backup_path = path.with_extension("txt.bak");
+            fs::copy(path, &backup_path).map_err(|e| format!("{}: {}", backup_path.display(), e))?;
+        }
+
+        let mut header = Section::new("highscores");
+        header.set("version", FILE_VERSION);
+        let sections = Some(header).into_iter()
+            .chain(self.scores.iter().map(HighScore::to_section))
+            .collect::<Vec<_>>();
+
+        config::save(&path.to_string_lossy(), "Nihil Chroma high scores", &sections)
+    }
+}
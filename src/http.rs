@@ -0,0 +1,147 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+// Requests give up after this long so a dead server can't hang a thread forever
+const TIMEOUT: Duration = Duration::from_secs(5);
+// Bodies are small config files, anything bigger is a broken or hostile peer
+const MAX_BODY_SIZE: usize = 1 << 20;
+
+/// The parts of an `http://host:port/path` url
+#[derive(Clone, Debug, PartialEq)]
+pub struct Url {
+    pub host: String,
+    pub port: u16,
+    // Path without a trailing slash, empty for the root
+    pub path: String,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> Result<Url, String> {
+        let rest = url.strip_prefix("http://")
+            .ok_or_else(|| format!("only http:// urls are supported: {}", url))?;
+
+        let (address, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+        let (host, port) = match address.rfind(':') {
+            Some(i) => (
+                &address[..i],
+                address[i + 1..].parse::<u16>().map_err(|_| format!("invalid port in {}", url))?,
+            ),
+            None => (address, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("no host in {}", url));
+        }
+
+        Ok(Url {
+            host: host.to_string(),
+            port,
+            path: path.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+/// A request or response: its first line, headers and body
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub start_line: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Message {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn read_line<R: Read>(reader: &mut BufReader<R>) -> Result<String, String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Reads one message. Responses without a Content-Length header last until the
+/// connection is closed, requests without one have no body
+pub fn read_message<R: Read>(reader: R) -> Result<Message, String> {
+    let mut reader = BufReader::new(reader);
+
+    let start_line = read_line(&mut reader)?;
+    if start_line.is_empty() {
+        return Err("empty message".to_string());
+    }
+
+    let mut headers = vec!();
+    loop {
+        let line = read_line(&mut reader)?;
+        if line.is_empty() {
+            break;
+        }
+        let separator = line.find(':').ok_or_else(|| format!("invalid header: {}", line))?;
+        headers.push((line[..separator].trim().to_string(), line[separator + 1..].trim().to_string()));
+    }
+
+    let mut message = Message { start_line, headers, body: String::new() };
+    let mut body = vec!();
+    match message.header("Content-Length") {
+        Some(length) => {
+            let length = length.parse::<usize>().map_err(|_| format!("invalid content length {}", length))?;
+            if length > MAX_BODY_SIZE {
+                return Err(format!("body of {} bytes is too large", length));
+            }
+            body.resize(length, 0);
+            reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+        }
+        None => if message.start_line.starts_with("HTTP/") {
+            reader.take(MAX_BODY_SIZE as u64 + 1).read_to_end(&mut body).map_err(|e| e.to_string())?;
+            if body.len() > MAX_BODY_SIZE {
+                return Err("body is too large".to_string());
+            }
+        },
+    }
+    message.body = String::from_utf8(body).map_err(|_| "body is not utf-8".to_string())?;
+    Ok(message)
+}
+
+/// Writes a message with `headers` and the headers that describe the body
+pub fn write_message<W: Write>(mut writer: W, start_line: &str, headers: &[(&str, &str)], body: &str) -> Result<(), String> {
+    let mut text = format!("{}\r\n", start_line);
+    for &(name, value) in headers {
+        text.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    text.push_str(&format!(
+        "Content-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    ));
+    writer.write_all(text.as_bytes())
+        .and_then(|_| writer.flush())
+        .map_err(|e| e.to_string())
+}
+
+/// Sends a request to `path` under `url` and returns the status code and body of
+/// the response. Errors mean that no response came back
+pub fn request(url: &Url, method: &str, path: &str, body: &str) -> Result<(u16, String), String> {
+    let address = (url.host.as_str(), url.port).to_socket_addrs()
+        .map_err(|e| format!("{}: {}", url.host, e))?
+        .next()
+        .ok_or_else(|| format!("{} has no address", url.host))?;
+
+    let stream = TcpStream::connect_timeout(&address, TIMEOUT).map_err(|e| format!("{}: {}", url.host, e))?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+
+    let start_line = format!("{} {}{} HTTP/1.0", method, url.path, path);
+    write_message(&stream, &start_line, &[("Host", &url.host)], body)?;
+
+    let response = read_message(&stream)?;
+    let status = response.start_line.split_whitespace().nth(1)
+        .and_then(|status| status.parse::<u16>().ok())
+        .ok_or_else(|| format!("invalid status line: {}", response.start_line))?;
+    Ok((status, response.body))
+}
@@ -1,19 +1,28 @@
 extern crate specs;
 
-use nalgebra::Vector2;
+use nalgebra::{Vector2, Norm};
 use sdl2::EventPump;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use specs::Join;
 
+use camera::Camera;
 use components::{Transform, Velocity};
-use constants::*;
-use player;
+use cone::ConeMode;
 use player::PlayerComponent;
+use settings::Settings;
+use viewport::Viewport;
+
+// Players in the movement cone mode have to move at least this fast to turn
+const MIN_TURN_SPEED: f32 = 1.;
 
 pub struct InputSystem {
     pub event_pump: EventPump,
+    // In window coordinates
     pub mouse_pos: Vector2<f32>,
+    // Keys that went down and text that was typed since the last run, for menus
+    pub pressed: Vec<Keycode>,
+    pub text: String,
 
     pub should_exit: bool
 }
@@ -21,22 +30,31 @@ pub struct InputSystem {
 impl<'a> specs::System<'a> for InputSystem {
     type SystemData = (
         specs::WriteStorage<'a, PlayerComponent>,
-        specs::WriteStorage<'a, Velocity>,
         specs::WriteStorage<'a, Transform>,
+        specs::ReadStorage<'a, Velocity>,
+        specs::Fetch<'a, Camera>,
+        specs::Fetch<'a, Settings>,
+        specs::Fetch<'a, Viewport>,
     );
-    fn run(&mut self, (mut players, mut velocities, mut transforms): Self::SystemData) {
+    fn run(&mut self, (mut players, mut transforms, velocities, camera, settings, viewport): Self::SystemData) {
         //Run the event loop and store all the keycodes that were pressed
         let mut keys = Vec::<(Keycode, bool)>::new();
+        self.pressed.clear();
+        self.text.clear();
 
         for event in self.event_pump.poll_iter() {
             match event {
-                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                Event::Quit {..} => {
                     self.should_exit = true;
                     return;
                 },
                 Event::KeyDown { keycode: Some(code), .. } => {
                     keys.push((code, true));
+                    self.pressed.push(code);
                 },
+                Event::TextInput { text, .. } => {
+                    self.text.push_str(&text);
+                }
                 Event::KeyUp { keycode: Some(code), .. } => {
                     keys.push((code, false));
                 }
@@ -47,40 +65,24 @@ impl<'a> specs::System<'a> for InputSystem {
             }
         }
 
-        for (mut player_component, mut velocity, mut transform) in (&mut players, &mut velocities, &mut transforms).join() {
-            for key in &keys {
-                let keycode: Option<player::Keys> = match key.0{
-                    Keycode::W => Some(player::Keys::Up),
-                    Keycode::S => Some(player::Keys::Down),
-                    Keycode::D => Some(player::Keys::Right),
-                    Keycode::A => Some(player::Keys::Left),
-                    _ => None
-                };
+        let mouse_pos = viewport.window_to_game(self.mouse_pos);
 
-                match keycode{
-                    Some(code) => player_component.set_key(code, key.1),
-                    None => {}
-                };
+        for (player_component, transform, velocity) in (&mut players, &mut transforms, &velocities).join() {
+            for key in &keys {
+                if let Some(action) = settings.action_for(key.0) {
+                    player_component.set_key(action, key.1);
+                }
             }
 
-            let add_vel = 15.;
-            //All keys have been handled, let's use them
-            if player_component.get_key(player::Keys::Up) {
-                velocity.0 += Vector2::new(0.0, -add_vel);
-            }
-            if player_component.get_key(player::Keys::Down) {
-                velocity.0 += Vector2::new(0.0, add_vel);
-            }
-            if player_component.get_key(player::Keys::Right) {
-                velocity.0 += Vector2::new(add_vel, 0.0);
-            }
-            if player_component.get_key(player::Keys::Left) {
-                velocity.0 += Vector2::new(-add_vel, 0.0);
+            match settings.cone_mode {
+                ConeMode::Mouse => {
+                    let pos_diff = camera.screen_to_world(mouse_pos) - transform.pos;
+                    transform.angle = pos_diff.y.atan2(pos_diff.x) as f64;
+                }
+                ConeMode::Movement => if velocity.0.norm() > MIN_TURN_SPEED {
+                    transform.angle = velocity.0.y.atan2(velocity.0.x) as f64;
+                },
             }
-
-            let pos_diff = self.mouse_pos / UPSCALING as f32 - transform.pos;
-
-            transform.angle = pos_diff.y.atan2(pos_diff.x) as f64;
         }
     }
 }
@@ -0,0 +1,420 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bytes::fnv1a;
+use config::{self, Section};
+use highscore::HighScore;
+use http::{self, Url};
+
+// Seconds to wait before sending queued scores again after a failure. The wait
+// doubles after every failure up to the maximum
+const FIRST_RETRY_DELAY: u64 = 5;
+const MAX_RETRY_DELAY: u64 = 300;
+
+/// A finished run as sent to a leaderboard
+#[derive(Clone, Debug, PartialEq)]
+pub struct Submission {
+    pub score: HighScore,
+    // Checksum of the score so that the server can notice scores that got
+    // garbled on the way. Anyone can compute it, so it proves nothing about
+    // whether the run really happened
+    pub checksum: u64,
+}
+
+impl Submission {
+    pub fn new(score: HighScore) -> Submission {
+        let checksum = checksum(&score);
+        Submission { score, checksum }
+    }
+
+    pub fn from_section(section: &Section) -> Result<Submission, String> {
+        let hash = section.get_str("checksum").ok_or("score without a checksum")?;
+        Ok(Submission {
+            score: HighScore::from_section(section)?,
+            checksum: u64::from_str_radix(hash, 16).map_err(|_| format!("invalid checksum {}", hash))?,
+        })
+    }
+
+    pub fn to_section(&self) -> Section {
+        let mut section = self.score.to_section();
+        section.set("checksum", format!("{:016x}", self.checksum));
+        section
+    }
+
+    pub fn checksum_matches(&self) -> bool {
+        self.checksum == checksum(&self.score)
+    }
+}
+
+/// 64 bit FNV-1a hash of the details of a run
+pub fn checksum(score: &HighScore) -> u64 {
+    let text = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        score.name, score.points, score.date, score.seed, score.duration, score.max_combo
+    );
+    fnv1a(text.as_bytes())
+}
+
+/// What a leaderboard did with a submission that reached it
+#[derive(Clone, Debug, PartialEq)]
+pub enum Submitted {
+    Accepted,
+    // The leaderboard will never take this score, so there's no use in retrying
+    Rejected(String),
+}
+
+/// Somewhere to send scores and get the best ones from. Errors mean that the
+/// leaderboard couldn't be reached and the request may work later
+pub trait Leaderboard {
+    fn submit(&mut self, submission: &Submission) -> Result<Submitted, String>;
+    fn top(&mut self, count: usize) -> Result<Vec<HighScore>, String>;
+}
+
+/// A leaderboard server that speaks HTTP. Scores are sent and received as
+/// `[score]` sections in the format of the data files
+pub struct HttpLeaderboard {
+    url: Url,
+}
+
+impl HttpLeaderboard {
+    pub fn new(url: &str) -> Result<HttpLeaderboard, String> {
+        Ok(HttpLeaderboard { url: Url::parse(url)? })
+    }
+}
+
+impl Leaderboard for HttpLeaderboard {
+    fn submit(&mut self, submission: &Submission) -> Result<Submitted, String> {
+        let body = config::format(&[submission.to_section()]);
+        match http::request(&self.url, "POST", "/scores", &body)? {
+            (200, _) => Ok(Submitted::Accepted),
+            // Client errors won't go away by sending the same thing again
+            (status, body) if (400..500).contains(&status) => {
+                Ok(Submitted::Rejected(format!("{} {}", status, body.trim())))
+            }
+            (status, body) => Err(format!("{} {}", status, body.trim())),
+        }
+    }
+
+    fn top(&mut self, count: usize) -> Result<Vec<HighScore>, String> {
+        match http::request(&self.url, "GET", &format!("/scores?count={}", count), "")? {
+            (200, body) => config::parse(&body)?.iter()
+                .filter(|section| section.name == "score")
+                .map(HighScore::from_section)
+                .collect(),
+            (status, body) => Err(format!("{} {}", status, body.trim())),
+        }
+    }
+}
+
+/// The best online scores, as far as the game knows
+#[derive(Clone, Debug, PartialEq)]
+pub enum TopScores {
+    NotFetched,
+    Loading,
+    Fetched(Vec<HighScore>),
+    Failed(String),
+}
+
+enum Request {
+    Submit(Submission),
+    FetchTop(usize),
+}
+
+/// Talks to a leaderboard on a thread of its own so the game never waits for
+/// the network. Submissions that can't be sent are queued, saved to disk and
+/// retried until they get through
+pub struct LeaderboardClient {
+    requests: Sender<Request>,
+    responses: Receiver<Result<Vec<HighScore>, String>>,
+    pub top: TopScores,
+}
+
+impl LeaderboardClient {
+    /// Starts the client thread. Queued submissions are kept in `queue_path`
+    /// between runs of the game, or only in memory without one
+    pub fn start<L: Leaderboard + Send + 'static>(leaderboard: L, queue_path: Option<PathBuf>) -> LeaderboardClient {
+        let (request_sender, request_receiver) = mpsc::channel();
+        let (response_sender, response_receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut worker = Worker::new(leaderboard, queue_path);
+            worker.run(request_receiver, response_sender);
+        });
+
+        LeaderboardClient {
+            requests: request_sender,
+            responses: response_receiver,
+            top: TopScores::NotFetched,
+        }
+    }
+
+    pub fn submit(&self, submission: Submission) {
+        // The thread only stops if it panicked, the score is lost either way
+        let _ = self.requests.send(Request::Submit(submission));
+    }
+
+    /// Starts fetching the best scores, which end up in `top`
+    pub fn fetch_top(&mut self, count: usize) {
+        self.top = match self.requests.send(Request::FetchTop(count)) {
+            Ok(_) => TopScores::Loading,
+            Err(_) => TopScores::Failed("the leaderboard client stopped".to_string()),
+        };
+    }
+
+    /// Picks up the answers that have arrived since the last update
+    pub fn update(&mut self) {
+        loop {
+            match self.responses.try_recv() {
+                Ok(Ok(scores)) => self.top = TopScores::Fetched(scores),
+                Ok(Err(e)) => {
+                    println!("Could not fetch the leaderboard: {}", e);
+                    self.top = TopScores::Failed(e);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if self.top == TopScores::Loading {
+                        self.top = TopScores::Failed("the leaderboard client stopped".to_string());
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+struct Worker<L: Leaderboard> {
+    leaderboard: L,
+    queue: Vec<Submission>,
+    queue_path: Option<PathBuf>,
+    retry_delay: u64,
+    next_attempt: Instant,
+}
+
+impl<L: Leaderboard> Worker<L> {
+    fn new(leaderboard: L, queue_path: Option<PathBuf>) -> Worker<L> {
+        let queue = match queue_path {
+            Some(ref path) if path.exists() => load_queue(path).unwrap_or_else(|e| {
+                println!("Could not read the queued leaderboard scores: {}", e);
+                vec!()
+            }),
+            _ => vec!(),
+        };
+
+        Worker {
+            leaderboard,
+            queue,
+            queue_path,
+            retry_delay: FIRST_RETRY_DELAY,
+            next_attempt: Instant::now(),
+        }
+    }
+
+    fn run(&mut self, requests: Receiver<Request>, responses: Sender<Result<Vec<HighScore>, String>>) {
+        loop {
+            self.send_queue();
+
+            // Sleep until the next request, or until it is time to retry
+            let request = if self.queue.is_empty() {
+                match requests.recv() {
+                    Ok(request) => request,
+                    Err(_) => return,
+                }
+            } else {
+                let now = Instant::now();
+                let wait = if self.next_attempt > now { self.next_attempt - now } else { Duration::from_secs(0) };
+                match requests.recv_timeout(wait) {
+                    Ok(request) => request,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => return,
+                }
+            };
+
+            match request {
+                Request::Submit(submission) => {
+                    self.queue.push(submission);
+                    self.save_queue();
+                }
+                Request::FetchTop(count) => {
+                    if responses.send(self.leaderboard.top(count)).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends queued submissions in order until one fails
+    fn send_queue(&mut self) {
+        if self.queue.is_empty() || Instant::now() < self.next_attempt {
+            return;
+        }
+
+        while !self.queue.is_empty() {
+            match self.leaderboard.submit(&self.queue[0]) {
+                Ok(Submitted::Accepted) => {
+                    self.queue.remove(0);
+                    self.retry_delay = FIRST_RETRY_DELAY;
+                }
+                Ok(Submitted::Rejected(reason)) => {
+                    println!("The leaderboard rejected a score: {}", reason);
+                    self.queue.remove(0);
+                }
+                Err(e) => {
+                    println!("Could not reach the leaderboard, retrying in {} s: {}", self.retry_delay, e);
+                    self.next_attempt = Instant::now() + Duration::from_secs(self.retry_delay);
+                    self.retry_delay = (self.retry_delay * 2).min(MAX_RETRY_DELAY);
+                    break;
+                }
+            }
+        }
+        self.save_queue();
+    }
+
+    fn save_queue(&self) {
+        if let Some(ref path) = self.queue_path {
+            let sections = self.queue.iter().map(Submission::to_section).collect::<Vec<_>>();
+            let result = path.parent()
+                .map_or(Ok(()), |dir| fs::create_dir_all(dir).map_err(|e| e.to_string()))
+                .and_then(|_| config::save(&path.to_string_lossy(), "Scores waiting to be sent to the leaderboard", &sections));
+            if let Err(e) = result {
+                println!("Could not save the queued leaderboard scores: {}", e);
+            }
+        }
+    }
+}
+
+fn load_queue(path: &Path) -> Result<Vec<Submission>, String> {
+    config::load(&path.to_string_lossy())?.iter()
+        .filter(|section| section.name == "score")
+        .map(Submission::from_section)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::net::TcpListener;
+    use std::process;
+
+    use mock_leaderboard;
+
+    fn score(name: &str, points: i32) -> HighScore {
+        HighScore {
+            name: name.to_string(),
+            points,
+            date: 1_500_000_000,
+            seed: 1,
+            duration: 60.,
+            max_combo: 3,
+        }
+    }
+
+    // A url that refuses connections, by taking a free port and letting go of it
+    fn unreachable_url() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        format!("http://{}", listener.local_addr().unwrap())
+    }
+
+    fn queue_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("nihil-chroma-{}-{}.txt", name, process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn valid_submission_is_accepted() {
+        let mut leaderboard = HttpLeaderboard::new(&mock_leaderboard::start().unwrap()).unwrap();
+        let submitted = leaderboard.submit(&Submission::new(score("alice", 100)));
+        assert_eq!(submitted, Ok(Submitted::Accepted));
+        assert_eq!(leaderboard.top(10), Ok(vec!(score("alice", 100))));
+    }
+
+    #[test]
+    fn wrong_checksum_is_rejected() {
+        let mut leaderboard = HttpLeaderboard::new(&mock_leaderboard::start().unwrap()).unwrap();
+        let mut submission = Submission::new(score("alice", 100));
+        submission.score.points = 1_000_000;
+
+        match leaderboard.submit(&submission) {
+            Ok(Submitted::Rejected(reason)) => assert!(reason.starts_with("400"), "{}", reason),
+            other => panic!("expected a rejection, got {:?}", other),
+        }
+        assert_eq!(leaderboard.top(10), Ok(vec!()));
+    }
+
+    #[test]
+    fn top_scores_are_sorted() {
+        let mut leaderboard = HttpLeaderboard::new(&mock_leaderboard::start().unwrap()).unwrap();
+        for &(name, points) in &[("alice", 200), ("bob", 500), ("carol", 300)] {
+            assert_eq!(leaderboard.submit(&Submission::new(score(name, points))), Ok(Submitted::Accepted));
+        }
+        assert_eq!(leaderboard.top(2), Ok(vec!(score("bob", 500), score("carol", 300))));
+    }
+
+    #[test]
+    fn client_submits_and_fetches() {
+        let leaderboard = HttpLeaderboard::new(&mock_leaderboard::start().unwrap()).unwrap();
+        let mut client = LeaderboardClient::start(leaderboard, None);
+        client.submit(Submission::new(score("alice", 100)));
+        client.fetch_top(10);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while client.top == TopScores::Loading && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            client.update();
+        }
+        assert_eq!(client.top, TopScores::Fetched(vec!(score("alice", 100))));
+    }
+
+    #[test]
+    fn client_saves_scores_it_cannot_send() {
+        let path = queue_path("client-queue");
+        let client = LeaderboardClient::start(HttpLeaderboard::new(&unreachable_url()).unwrap(), Some(path.clone()));
+        client.submit(Submission::new(score("alice", 100)));
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut queue = load_queue(&path);
+        while queue != Ok(vec!(Submission::new(score("alice", 100)))) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+            queue = load_queue(&path);
+        }
+        assert_eq!(queue, Ok(vec!(Submission::new(score("alice", 100)))));
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn unsent_scores_are_retried_with_backoff() {
+        let path = queue_path("worker-queue");
+        let mut worker = Worker::new(HttpLeaderboard::new(&unreachable_url()).unwrap(), Some(path.clone()));
+        worker.queue.push(Submission::new(score("alice", 100)));
+
+        worker.send_queue();
+        assert_eq!(worker.queue.len(), 1);
+        assert_eq!(worker.retry_delay, FIRST_RETRY_DELAY * 2);
+        assert!(worker.next_attempt > Instant::now());
+        assert_eq!(load_queue(&path).map(|queue| queue.len()), Ok(1));
+
+        // Nothing is sent before the next attempt is due
+        worker.send_queue();
+        assert_eq!(worker.retry_delay, FIRST_RETRY_DELAY * 2);
+
+        worker.next_attempt = Instant::now();
+        worker.send_queue();
+        assert_eq!(worker.retry_delay, FIRST_RETRY_DELAY * 4);
+
+        // Once the leaderboard can be reached the queue is sent and emptied
+        worker.leaderboard = HttpLeaderboard::new(&mock_leaderboard::start().unwrap()).unwrap();
+        worker.next_attempt = Instant::now();
+        worker.send_queue();
+        assert!(worker.queue.is_empty());
+        assert_eq!(worker.retry_delay, FIRST_RETRY_DELAY);
+        assert_eq!(load_queue(&path), Ok(vec!()));
+        assert_eq!(worker.leaderboard.top(10), Ok(vec!(score("alice", 100))));
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,162 @@
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = MIN_MATCH + 255;
+const WINDOW: usize = 65535;
+// Earlier positions tried for each match. More finds longer matches but packing
+// gets slower
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24;
+    (value.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize
+}
+
+/// LZ77 style compression for the asset archive. The result is a series of
+/// groups, each starting with a byte whose bits tell whether the next eight
+/// items are a literal byte (0) or a match (1). Matches are three bytes: the
+/// distance back to the earlier copy as a little endian u16, then the length
+/// minus MIN_MATCH
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 2);
+    // Latest position with each hash, and the position before it with the same
+    // hash for every position
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; input.len()];
+
+    let mut flags_at = 0;
+    let mut items = 8;
+    let mut pos = 0;
+    while pos < input.len() {
+        if items == 8 {
+            flags_at = output.len();
+            output.push(0);
+            items = 0;
+        }
+
+        let mut best = (0, 0);
+        if pos + MIN_MATCH <= input.len() {
+            let max_length = MAX_MATCH.min(input.len() - pos);
+            let mut candidate = head[hash(&input[pos..])];
+            let mut chain = 0;
+            while candidate != usize::MAX && pos - candidate <= WINDOW && chain < MAX_CHAIN {
+                let length = input[candidate..].iter()
+                    .zip(&input[pos..pos + max_length])
+                    .take_while(|&(a, b)| a == b)
+                    .count();
+                if length > best.1 {
+                    best = (pos - candidate, length);
+                }
+                candidate = previous[candidate];
+                chain += 1;
+            }
+        }
+
+        let advance = if best.1 >= MIN_MATCH {
+            output[flags_at] |= 1 << items;
+            output.push(best.0 as u8);
+            output.push((best.0 >> 8) as u8);
+            output.push((best.1 - MIN_MATCH) as u8);
+            best.1
+        } else {
+            output.push(input[pos]);
+            1
+        };
+        for p in pos..(pos + advance).min(input.len().saturating_sub(MIN_MATCH - 1)) {
+            let h = hash(&input[p..]);
+            previous[p] = head[h];
+            head[h] = p;
+        }
+        pos += advance;
+        items += 1;
+    }
+    output
+}
+
+/// Decompresses data that is `size` bytes long when decompressed
+pub fn decompress(input: &[u8], size: usize) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(size);
+    let mut pos = 0;
+    while output.len() < size {
+        let flags = *input.get(pos).ok_or("compressed data ends early")?;
+        pos += 1;
+
+        for bit in 0..8 {
+            if output.len() == size {
+                break;
+            }
+            if flags & (1 << bit) == 0 {
+                output.push(*input.get(pos).ok_or("compressed data ends early")?);
+                pos += 1;
+            } else {
+                let item = input.get(pos..pos + 3).ok_or("compressed data ends early")?;
+                let distance = item[0] as usize | (item[1] as usize) << 8;
+                let length = item[2] as usize + MIN_MATCH;
+                pos += 3;
+                if distance == 0 || distance > output.len() || output.len() + length > size {
+                    return Err("compressed data is corrupt".to_string());
+                }
+                // Copied a byte at a time since the copy may overlap what it adds
+                let start = output.len() - distance;
+                for i in 0..length {
+                    let byte = output[start + i];
+                    output.push(byte);
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(input: &[u8]) -> Vec<u8> {
+        let compressed = compress(input);
+        assert_eq!(decompress(&compressed, input.len()).unwrap(), input);
+        compressed
+    }
+
+    #[test]
+    fn empty_input() {
+        assert_eq!(round_trip(b""), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn shorter_than_a_match() {
+        round_trip(b"a");
+        round_trip(b"abc");
+        round_trip(b"aaaa");
+    }
+
+    #[test]
+    fn long_runs() {
+        // A match that overlaps the bytes it copies
+        let compressed = round_trip(&[7; 10_000]);
+        assert!(compressed.len() < 200);
+        // Longer than one match can be
+        let repeated = b"nihil chroma ".iter().cycle().take(MAX_MATCH * 3).cloned().collect::<Vec<_>>();
+        assert!(round_trip(&repeated).len() < repeated.len() / 10);
+    }
+
+    #[test]
+    fn incompressible_input() {
+        let mut state = 0x2545_f491_u32;
+        let input = (0..5000).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }).collect::<Vec<_>>();
+        // Only a flag byte for every eight literals is added
+        assert!(round_trip(&input).len() <= input.len() + input.len() / 8 + 1);
+    }
+
+    #[test]
+    fn damaged_input() {
+        let compressed = compress(&[7; 100]);
+        assert!(decompress(&compressed[..compressed.len() - 1], 100).is_err());
+        // A match that reaches back before the start
+        assert!(decompress(&[1, 5, 0, 0], 4).is_err());
+    }
+}
@@ -14,163 +14,382 @@ mod player;
 mod rendering;
 mod components;
 mod input;
+mod leaderboard;
+mod archive;
+mod assets;
+mod audio;
+mod bounds;
+mod bytes;
+mod camera;
 mod collision;
-
+mod dash;
+mod error;
+mod mock_leaderboard;
+mod movement;
+mod paths;
+mod music;
+mod options;
+mod cone;
+mod physics;
+mod ron;
+mod config;
+mod director;
+mod script;
+mod powerup;
+mod health;
+mod lz;
+mod highscore;
+mod http;
+mod score;
+mod screens;
+mod settings;
+mod shake;
+mod spatial;
+mod spawner;
+mod ui;
+mod watcher;
+mod viewport;
+
+use assets::Assets;
+use audio::{AudioManager, BallSoundSystem, SoundGroup, SoundQueue};
+use bounds::{BoundsSystem, EdgeMode, PlayerBounds};
+use camera::{Camera, CameraSystem, ScreenSpace};
 use collision::CollisionSystem;
-use components::{Transform, Velocity, MaxVelocity, BallType, BoundingCircle, ObamaComponent, OrbitComponent};
-use components::{HitBad, HitNeutral, HitGood, ScreenShake};
+use components::{Transform, Velocity, MaxVelocity, BallType, BoundingCircle, ObamaComponent, OrbitComponent, RigidBody, Lifetime};
+use components::{HitBad, HitNeutral, HitGood, ScreenShake, TimeScale};
+use cone::ConeSize;
+use dash::{Dash, DashSystem};
 use constants::*;
-use game::{RespawnComponent, MaxVelSystem, MotionSystem, ObamaSystem, OrbitSystem, RespawnSystem};
+use error::GameError;
+use health::{DamageEvents, Health, HealthSystem};
+use highscore::{HighScore, HighScoreTable};
+use game::{RespawnComponent, LifetimeSystem, MaxVelSystem, MotionSystem, ObamaSystem, OrbitSystem, RespawnSystem};
+use director::{Director, SpawnRules, WaveEvent};
 use input::InputSystem;
+use leaderboard::{HttpLeaderboard, LeaderboardClient, Submission};
+use movement::{Movement, MovementSystem};
+use music::{MusicBeat, MusicDirector, MusicState};
+use options::{MenuAction, OptionsMenu};
+use physics::{Arena, PhysicsSystem};
+use powerup::{ActiveEffects, PowerUpSystem};
+use score::{Score, ScorePopups, ScoreSystem};
+use screens::TextScreen;
+use settings::{Settings, SettingsFile};
+use shake::{CameraShake, ShakeSystem};
+use script::{Prefab, ScriptCommand, ScriptContext, ScriptRunner};
+use spatial::{EmittedSounds, SoundEmitter, SpatialAudioSystem};
+use spawner::{BallEvents, BallPool, BallSpawner};
 use player::PlayerComponent;
 use sprite::{Sprite, TextureId, TextureManager};
 use rendering::RenderingSystem;
+use viewport::WindowMode;
+use watcher::FileWatcher;
 
-use rand::Rng;
-
-use std::fs;
+use std::collections::HashMap;
+use std::env;
 use std::path::Path;
+use std::rc::Rc;
 
 use nalgebra::{Vector2, zero};
 
+use sdl2::keyboard::Keycode;
 use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::surface::Surface;
-use sdl2::mixer::{AUDIO_S16LSB, DEFAULT_CHANNELS, INIT_OGG};
+use sdl2::messagebox::{self, MESSAGEBOX_ERROR};
+use sdl2::mixer::{AUDIO_S16LSB, DEFAULT_CHANNELS, INIT_OGG, Sdl2MixerContext};
+use sdl2::rwops::RWops;
 
 use specs::{Entity, RunNow, World};
 
 // use sfml::audio::{Music};
 
-struct BallSpawner {
-    spawn_time: f32,
-    last_spawn: f32,
+fn create_text_entity(texture: TextureId, world: &mut World) -> Entity {
+    // Create text entity
+    world.create_entity()
+        .with(Transform {
+            pos: Vector2::new(140.0, 10.0),
+            angle: 0.0,
+            scale: Vector2::new(0.1, 0.1)
+        })
+        .with(Sprite::new(texture))
+        .with(ScreenSpace)
+        .build()
+}
 
-    types: Vec<(BallType, Sprite)>,
+fn create_popup_entity(texture: TextureId, pos: Vector2<f32>, world: &mut World) -> Entity {
+    world.create_entity()
+        .with(Transform {
+            pos,
+            angle: 0.0,
+            scale: Vector2::new(0.08, 0.08)
+        })
+        .with(Velocity(Vector2::new(0.0, -20.0)))
+        .with(Lifetime(1.0))
+        .with(Sprite::new(texture))
+        .build()
 }
 
-impl BallSpawner {
-    pub fn new(types: Vec<(BallType, Sprite)>) -> BallSpawner {
-        BallSpawner {
-            spawn_time: 3.,
-            last_spawn: 0.,
+/// Makes the window, audio and screen shake follow the settings. The window is
+/// only resized when its settings differ from `old_settings`, so that sizes
+/// picked by dragging the window edges stay
+fn apply_settings(
+    settings: &Settings,
+    old_settings: Option<&Settings>,
+    rendering: &mut RenderingSystem,
+    audio: &mut AudioManager,
+    world: &mut World,
+) {
+    audio.master_volume = settings.master_volume;
+    for &group in &SoundGroup::all() {
+        audio.set_volume(group, settings.volume(group));
+    }
 
-            types: types,
+    rendering.scale_mode = settings.scale_mode;
+    let window_changed = match old_settings {
+        Some(old) => old.window_mode != settings.window_mode || old.scale != settings.scale,
+        None => true,
+    };
+    if window_changed {
+        let window = rendering.canvas.window_mut();
+        let fullscreen = settings.window_mode.fullscreen_type();
+        if window.fullscreen_state() != fullscreen {
+            if let Err(e) = window.set_fullscreen(fullscreen) {
+                println!("Could not change fullscreen mode: {}", e);
+            }
+        }
+        let size = (RESOLUTION.0 * settings.scale, RESOLUTION.1 * settings.scale);
+        if settings.window_mode == WindowMode::Windowed && window.size() != size {
+            if let Err(e) = window.set_size(size.0, size.1) {
+                println!("Could not resize the window: {}", e);
+            }
         }
     }
 
-    pub fn do_spawn(&mut self, world: &mut World) {
-        let curr_time = time::precise_time_s() as f32;
-        if curr_time > self.last_spawn + self.spawn_time {
-            self.spawn_ball(world);
-
-            self.last_spawn = curr_time;
-        }
+    // Only set when it changed so that `--shake` stays in effect until the
+    // player picks another strength
+    let shake_changed = match old_settings {
+        Some(old) => old.shake != settings.shake,
+        None => true,
+    };
+    if shake_changed {
+        world.write_resource::<CameraShake>().scale = settings.shake;
     }
+    *world.write_resource::<Settings>() = settings.clone();
+}
 
-    pub fn spawn_ball(&self, world: &mut World) {
-        let mut rng = rand::thread_rng();
+/// What changing the settings while the game runs touches
+struct SettingsContext<'a, 'l: 'a, 's: 'l> {
+    settings: &'a mut Settings,
+    settings_file: &'a SettingsFile,
+    rendering: &'a mut RenderingSystem<'l, 's>,
+    audio: &'a mut AudioManager,
+    world: &'a mut World,
+}
 
-        let respawn_comp = RespawnComponent{max_radius: 400.0, max_speed: 120.0, min_speed: 70.0};
-        let transform = Transform {
-            pos: Vector2::new(600.0, 600.0),
-            angle: 0.0,
-            scale: Vector2::new(0.25, 0.25)
-        };
-        let bound = components::BoundingCircle { radius: 28.0 * 0.5 };
-        let (ball_type, ball_sprite) = self.types[rng.gen_range(0, self.types.len())];
-
-        world.create_entity()
-            .with(transform)
-            .with(Velocity(Vector2::new(0.0, 0.0)))
-            .with(ball_sprite)
-            .with(respawn_comp)
-            .with(bound)
-            .with(ball_type)
-            .build();
+impl<'a, 'l, 's> SettingsContext<'a, 'l, 's> {
+    fn apply(&mut self, old_settings: &Settings) {
+        apply_settings(self.settings, Some(old_settings), self.rendering, self.audio, self.world);
+    }
+
+    fn save(&self) {
+        if let Err(e) = self.settings_file.save(self.settings) {
+            println!("Could not save settings: {}", e);
+        }
     }
 }
 
-fn create_text_entity(texture: TextureId, world: &mut World) -> Entity {
-    // Create text entity
-    world.create_entity()
-        .with(Transform {
-            pos: Vector2::new(140.0, 10.0),
-            angle: 0.0,
-            scale: Vector2::new(0.1, 0.1)
-        })
-        .with(Sprite::new(texture))
-        .build()
+/// Runs a frame of the options menu and applies the settings if they changed.
+/// They are saved when the menu closes
+fn run_options_menu(
+    title: &str,
+    menu: &mut OptionsMenu,
+    pressed: &[Keycode],
+    context: &mut SettingsContext,
+) -> (Vec<String>, MenuAction) {
+    let old_settings = context.settings.clone();
+    let (lines, action) = menu.update(title, context.settings, pressed);
+
+    if *context.settings != old_settings {
+        context.apply(&old_settings);
+    }
+    if action != MenuAction::Stay {
+        context.save();
+    }
+    (lines, action)
 }
 
-// TODO: split this into multiple functions
-pub fn main() {
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let ttf_context = sdl2::ttf::init().unwrap();
+/// F11 switches between a window and fullscreen on every screen of the game
+fn toggle_fullscreen(pressed: &[Keycode], context: &mut SettingsContext) {
+    if !pressed.contains(&Keycode::F11) {
+        return;
+    }
+
+    let old_settings = context.settings.clone();
+    context.settings.window_mode = match context.settings.window_mode {
+        WindowMode::Windowed => WindowMode::Borderless,
+        WindowMode::Fullscreen | WindowMode::Borderless => WindowMode::Windowed,
+    };
+    context.apply(&old_settings);
+    context.save();
+}
 
+fn open_audio() -> Result<Sdl2MixerContext, GameError> {
     let frequency = 44_100;
     let format = AUDIO_S16LSB; // signed 16 bit samples, in little-endian byte order
     let channels = DEFAULT_CHANNELS; // Stereo
     let chunk_size = 1_024;
-    sdl2::mixer::open_audio(frequency, format, channels, chunk_size).unwrap();
-    let _mixer_context = sdl2::mixer::init(INIT_OGG).unwrap();
+    sdl2::mixer::open_audio(frequency, format, channels, chunk_size).map_err(GameError::Audio)?;
+    sdl2::mixer::init(INIT_OGG).map_err(GameError::Audio)
+}
+
+pub fn main() {
+    if let Err(e) = run() {
+        let message = match e.hint() {
+            Some(hint) => format!("{}\n\n{}", e, hint),
+            None => e.to_string(),
+        };
+        eprintln!("{}", message);
+        // Shown in a window as well since players may not have started the
+        // game from a terminal
+        if let Err(e) = messagebox::show_simple_message_box(MESSAGEBOX_ERROR, "Nihil Chroma", &message, None) {
+            eprintln!("Could not show the error: {}", e);
+        }
+        std::process::exit(1);
+    }
+}
+
+// TODO: split this into multiple functions
+fn run() -> Result<(), GameError> {
+    let args = env::args().collect::<Vec<_>>();
+
+    // `--dev` reloads assets and data files when they change on disk
+    let dev_mode = args.iter().any(|arg| arg == "--dev");
+
+    // `--assets <dir>` runs the game with the assets in another directory
+    let asset_root = assets::find_root(&args).map_err(GameError::asset(assets::MANIFEST))?;
+
+    // `--pack <file>` packs the loose assets into an archive for distribution.
+    // Packing happens before the settings are loaded so that it leaves them alone
+    if let Some(out) = args.iter().position(|arg| arg == "--pack").and_then(|i| args.get(i + 1)) {
+        let assets = Assets::load(&asset_root, false).map_err(GameError::asset(assets::MANIFEST))?;
+        let stats = assets.pack(Path::new(out)).map_err(GameError::asset(out))?;
+        println!(
+            "Packed {} files into {}, {} bytes from {}",
+            stats.files, out, stats.packed_size, stats.size
+        );
+        return Ok(());
+    }
+
+    let (settings_file, mut settings) = SettingsFile::load();
+
+    // The loose files are used in dev mode so that they can be edited
+    let assets = Rc::new(Assets::load(&asset_root, !dev_mode).map_err(GameError::asset(assets::MANIFEST))?);
+    let manifest_error = GameError::asset(assets::MANIFEST);
+
+    let sdl_context = sdl2::init().map_err(GameError::startup("SDL"))?;
+    let video_subsystem = sdl_context.video().map_err(GameError::startup("video"))?;
+    let ttf_context = sdl2::ttf::init().map_err(GameError::startup("font rendering"))?;
+
+    // Without a sound device the game is played in silence
+    let mixer_context = match open_audio() {
+        Ok(context) => Some(context),
+        Err(e) => {
+            println!("{}, playing without sound", e);
+            None
+        }
+    };
 
     let window = video_subsystem.window(
-        "rust-sdl2 demo: Video", RESOLUTION.0 * UPSCALING, RESOLUTION.1 * UPSCALING)
+        "rust-sdl2 demo: Video", RESOLUTION.0 * settings.scale, RESOLUTION.1 * settings.scale)
         .position_centered()
+        .resizable()
         .opengl()
         .build()
-        .unwrap();
+        .map_err(GameError::startup("the window"))?;
 
     // The renderer which actually renders to the game window
-    let canvas = window.into_canvas().build().unwrap();
+    let mut canvas_builder = window.into_canvas();
+    if settings.vsync {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+    let canvas = canvas_builder.build().map_err(GameError::startup("the renderer"))?;
 
     // Renderer where the game is rendered in full color
     let game_surface = Surface::new(
-        RESOLUTION.0, RESOLUTION.1, PixelFormatEnum::RGB888).unwrap();
-    let mut game_canvas = game_surface.into_canvas().unwrap();
+        RESOLUTION.0, RESOLUTION.1, PixelFormatEnum::RGB888).map_err(GameError::rendering)?;
+    let mut game_canvas = game_surface.into_canvas().map_err(GameError::rendering)?;
     game_canvas.set_draw_color(Color::RGB(200, 80, 50));
 
     let game_texture_creator = game_canvas.texture_creator();
     //let texture_registry_ref = Rc::new(RefCell::new(TextureRegistry::new()));
-    let mut texture_manager = TextureManager::new(&game_texture_creator);
-
-    let ball_data = vec! {
-        (BallType::Good, "data/good.png"),
-        (BallType::Neutral, "data/neutral.png"),
-        (BallType::Bad, "data/bad.png"),
-    };
-
-    let mut ball_spawner = BallSpawner::new(ball_data.into_iter().map(|(ball_type, ball_texture_file)| {
-        (ball_type, Sprite::new(texture_manager.load(ball_texture_file).unwrap()))
-    }).collect());
-
-    let sausage_sprite = Sprite::new(texture_manager.load("data/sausage.png").unwrap());
-    let sausage_transform = Transform {
-        pos: Vector2::new(100000000., RESOLUTION.1 as f32 / 2.0),
-        angle: 0.0,
-        scale: Vector2::new(1.5, 1.5)
-    };
-    let nuke_sprite = Sprite::new(texture_manager.load("data/nuke.png").unwrap());
-    let nuke_transform = Transform {
-        pos: Vector2::new(100000., RESOLUTION.1 as f32 / 2.0),
-        angle: 0.0,
-        scale: Vector2::new(0.25, 0.25)
+    let mut texture_manager = TextureManager::new(&game_texture_creator, assets.clone());
+
+    let balls_path = assets.config("balls").map_err(&manifest_error)?;
+    let ball_info = powerup::load_ball_info(&balls_path, &assets).map_err(GameError::asset(&balls_path))?;
+
+    let ball_sprites = ball_info.iter().map(|info| {
+        let path = assets.texture(&info.sprite).map_err(&manifest_error)?;
+        let texture = texture_manager.load_or_placeholder(&path).map_err(GameError::rendering)?;
+        Ok((info.ball_type, Sprite::new(texture)))
+    }).collect::<Result<Vec<_>, GameError>>()?;
+    let shard_sprite = ball_sprites.iter()
+        .find(|&&(ball_type, _)| ball_type == BallType::Good)
+        .ok_or_else(|| GameError::Asset { path: balls_path.clone(), message: "there is no good ball".into() })?
+        .1;
+    let mut ball_spawner = BallSpawner::new(ball_sprites);
+
+    let sausage_path = assets.texture("sausage").map_err(&manifest_error)?;
+    let nuke_path = assets.texture("nuke").map_err(&manifest_error)?;
+    let mut prefabs = HashMap::new();
+    prefabs.insert("sausage".to_string(), Prefab {
+        sprite: Sprite::new(texture_manager.load_or_placeholder(&sausage_path).map_err(GameError::rendering)?),
+        transform: Transform {
+            pos: Vector2::new(100000000., WORLD_SIZE.1 as f32 / 2.0),
+            angle: 0.0,
+            scale: Vector2::new(1.5, 1.5)
+        },
+        collision: None,
+    });
+    prefabs.insert("nuke".to_string(), Prefab {
+        sprite: Sprite::new(texture_manager.load_or_placeholder(&nuke_path).map_err(GameError::rendering)?),
+        transform: Transform {
+            pos: Vector2::new(100000., WORLD_SIZE.1 as f32 / 2.0),
+            angle: 0.0,
+            scale: Vector2::new(0.25, 0.25)
+        },
+        collision: Some((BoundingCircle { radius: 28.0 * 0.5 }, BallType::Bad)),
+    });
+    let scripts = script::load_scripts(&assets.scripts(), &assets).map_err(GameError::asset("scripts"))?;
+    let mut script_runner = ScriptRunner::new(scripts, prefabs).map_err(GameError::asset("scripts"))?;
+    let sounds_path = assets.config("sounds").map_err(&manifest_error)?;
+    let sound_list = audio::load_sounds(&sounds_path, &assets).map_err(GameError::asset(&sounds_path))?;
+    let mut audio = match mixer_context {
+        Some(_) => AudioManager::new(sound_list, assets.clone()),
+        None => AudioManager::silent(sound_list, assets.clone()),
     };
-    let mut sausage_is_spawned = false;
-    let mut nuke_is_spawned = false;
+    // Missing sounds are reported again when they are played
+    if let Err(e) = audio.preload_all() {
+        println!("Could not load sounds: {}", e);
+    }
 
-    let obama_files = fs::read_dir("data/obamas").unwrap();
-    let obama_sprites = obama_files.map(|file| {
-        Sprite::new(texture_manager.load(
-            file.unwrap().path().to_str().unwrap()).unwrap()
-        )
-    }).collect();
+    let obama_files = assets.sprite_set("obamas").map_err(&manifest_error)?;
+    let obama_sprites = obama_files.iter().map(|path| {
+        let texture = texture_manager.load_or_placeholder(path).map_err(GameError::rendering)?;
+        Ok(Sprite::new(texture))
+    }).collect::<Result<Vec<_>, GameError>>()?;
+    if obama_sprites.is_empty() {
+        return Err(GameError::Asset { path: assets::MANIFEST.into(), message: "there are no obamas".into() });
+    }
 
     // Create font
-    let font = ttf_context.load_font(&Path::new("data/font.ttf"), 128).unwrap();
-
-    let event_pump = sdl_context.event_pump().unwrap();
+    let font_path = assets.font("main").map_err(&manifest_error)?;
+    // The font reads from these bytes for as long as it is used
+    let font_bytes = assets.read(&font_path).map_err(GameError::asset(&font_path))?;
+    let font = RWops::from_bytes(&font_bytes)
+        .and_then(|rwops| ttf_context.load_font_from_rwops(rwops, 128))
+        .map_err(GameError::asset(&font_path))?;
+
+    let event_pump = sdl_context.event_pump().map_err(GameError::startup("input"))?;
     
     let mut world = World::new();
     world.register::<Transform>();
@@ -183,77 +402,372 @@ pub fn main() {
     world.register::<BallType>();
     world.register::<MaxVelocity>();
     world.register::<OrbitComponent>();
-
-    let good_texture = texture_manager.load("data/good.png").unwrap();
+    world.register::<RigidBody>();
+    world.register::<Lifetime>();
+    world.register::<Health>();
+    world.register::<Dash>();
+    world.register::<Movement>();
+    world.register::<ScreenSpace>();
+    world.register::<SoundEmitter>();
+
+    let player_path = assets.texture("player").map_err(&manifest_error)?;
+    let good_texture = texture_manager.load_or_placeholder(&player_path).map_err(GameError::rendering)?;
     let test_sprite = Sprite::new(good_texture);
     let sprite_scale = 0.25;
+    let player_spawn = Vector2::new(WORLD_SIZE.0 as f32 / 2., WORLD_SIZE.1 as f32 / 2.0);
     let player_transform = Transform {
-        pos: Vector2::new(RESOLUTION.0 as f32 / 2., RESOLUTION.1 as f32 / 2.0),
+        pos: player_spawn,
         angle: 0.0,
         scale: Vector2::new(sprite_scale, sprite_scale)
     };
 
     let player_box = BoundingCircle { radius: 56.0 * sprite_scale };
+    let movement_path = assets.config("movement").map_err(&manifest_error)?;
+    let player_movement = movement::load_movement(&movement_path, &assets).map_err(GameError::asset(&movement_path))?;
+
+    // Dashing makes the player invulnerable unless `--no-dash-iframes` is given
+    let dash_iframes = !args.iter().any(|arg| arg == "--no-dash-iframes");
+    // `--shake <scale>` overrides the screen shake setting for this run, `--shake off`
+    // disables it
+    let shake_override = args.iter()
+        .position(|arg| arg == "--shake")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|scale| if scale == "off" { Some(0.) } else { scale.parse::<f32>().ok() });
 
     // Create some entites with some components
     let player_entity = world.create_entity()
         .with(Velocity(Vector2::new(0.0, 0.0)))
-        .with(MaxVelocity(100.0))
+        .with(MaxVelocity(player_movement.top_speed))
+        .with(player_movement)
         .with(player_transform)
         .with(test_sprite)
         .with(PlayerComponent::new())
         .with(player_box)
+        .with(Health::new(3, player_spawn))
+        .with(Dash::new(dash_iframes))
         .build();
 
     // TODO: use the specs dispatcher with tread_local for the rendering system
     let mut motion_system = MotionSystem { frametime: 0. };
     let mut obama_system = ObamaSystem { too_few_obamas: false };
-    let mut rendering_system = RenderingSystem::new(canvas, game_canvas, player_entity, 5.0, texture_manager);
+    let mut rendering_system = RenderingSystem::new(canvas, game_canvas, player_entity, texture_manager);
     let mut input_system = InputSystem {
         event_pump: event_pump,
         should_exit: false,
         mouse_pos: zero(),
+        pressed: vec!(),
+        text: String::new(),
     };
-    let mut collision_system = CollisionSystem { player: player_entity, new_points: 0 };
-    let mut orbit_system = OrbitSystem { player: player_entity, nuke_angle: 0. };
+    let mut collision_system = CollisionSystem;
+    let mut health_system = HealthSystem { frametime: 0. };
+    let mut movement_system = MovementSystem { frametime: 0. };
+    let mut dash_system = DashSystem { frametime: 0., trail_sprite: test_sprite };
+    let mut orbit_system = OrbitSystem { player: player_entity };
     let mut max_vel_system = MaxVelSystem;
+    let mut bounds_system = BoundsSystem;
+    let mut camera_system = CameraSystem { frametime: 0., target: player_entity };
+    let mut shake_system = ShakeSystem { frametime: 0. };
+    let mut ball_sound_system = BallSoundSystem {
+        sounds: powerup::collect_sounds(&ball_info),
+    };
+    let mut spatial_audio_system = SpatialAudioSystem {
+        frametime: 0.,
+        emitters: powerup::emitters(&ball_info),
+    };
     let mut respawn_system = RespawnSystem;
+    let mut physics_system = PhysicsSystem;
+    let mut lifetime_system = LifetimeSystem { frametime: 0. };
+    let mut score_system = ScoreSystem { frametime: 0. };
+    let mut powerup_system = PowerUpSystem {
+        frametime: 0.,
+        durations: powerup::durations(&ball_info),
+        shard_sprite,
+    };
 
-    let mut points = 0;
-    let mut life = 3;
+    world.add_resource(BallEvents(vec!()));
+    world.add_resource(SoundQueue(vec!()));
+    world.add_resource(EmittedSounds { once: vec!(), loops: vec!() });
+    world.add_resource(MusicBeat::none());
+    world.add_resource(BallPool::new(40, 1.5));
+    world.add_resource(SpawnRules::default());
+    world.add_resource(ConeSize { base: 0.07, multiplier: 1. });
+    world.add_resource(ActiveEffects(HashMap::new()));
+    world.add_resource(DamageEvents(vec!()));
+    world.add_resource(TimeScale(1.));
+    world.add_resource(PlayerBounds(EdgeMode::Clamp));
+    world.add_resource(Camera {
+        bounds: Some((Vector2::new(0., 0.), Vector2::new(WORLD_SIZE.0 as f32, WORLD_SIZE.1 as f32))),
+        ..Camera::new(player_spawn)
+    });
+    world.add_resource(Score::new());
+    world.add_resource(ScorePopups(vec!()));
+    world.add_resource(HitBad(false));
+    world.add_resource(HitNeutral(false));
+    world.add_resource(HitGood(false));
+    world.add_resource(ScreenShake(None));
+    world.add_resource(CameraShake::new(1.));
+    world.add_resource(settings.clone());
+    world.add_resource(rendering_system.viewport());
 
-    let score_texture_id = rendering_system.texture_manager.make_text_texture(
-        "Score: 0", &font, None
-    ).unwrap();
-    create_text_entity(score_texture_id, &mut world);
+    apply_settings(&settings, None, &mut rendering_system, &mut audio, &mut world);
+    if let Some(scale) = shake_override {
+        world.write_resource::<CameraShake>().scale = scale;
+    }
+    let mut options_menu = OptionsMenu::new();
+
+    let mut highscores = HighScoreTable::load();
+    let mut text_screen = TextScreen::new();
+
+    // `--leaderboard <url>` sends scores to an online leaderboard as well,
+    // `--leaderboard mock` to a pretend one that runs inside the game
+    let leaderboard_url = args.iter()
+        .position(|arg| arg == "--leaderboard")
+        .and_then(|i| args.get(i + 1));
+    let started = leaderboard_url.map(|url| if url == "mock" {
+        // Scores sent to the mock leaderboard aren't kept between runs
+        mock_leaderboard::start()
+            .and_then(|url| HttpLeaderboard::new(&url))
+            .map(|http| LeaderboardClient::start(http, None))
+    } else {
+        let queue_path = paths::data_dir().ok().map(|dir| dir.join("leaderboard_queue.txt"));
+        HttpLeaderboard::new(url).map(|http| LeaderboardClient::start(http, queue_path))
+    });
+    // The leaderboard is optional, so the game is played without it if it
+    // can't be used
+    let mut leaderboard = match started {
+        Some(Ok(leaderboard)) => Some(leaderboard),
+        Some(Err(e)) => {
+            println!("Could not start the leaderboard, playing without it: {}", e);
+            None
+        }
+        None => None,
+    };
+    if let Some(ref mut leaderboard) = leaderboard {
+        // Only a few fit on the title screen next to the local scores
+        leaderboard.fetch_top(5);
+    }
+
+    // Title screen with the high scores, the game starts when enter is pressed
+    let mut title_options_open = false;
+    loop {
+        input_system.run_now(&world.res);
+        if input_system.should_exit {
+            return Ok(());
+        }
+        let mut context = SettingsContext {
+            settings: &mut settings,
+            settings_file: &settings_file,
+            rendering: &mut rendering_system,
+            audio: &mut audio,
+            world: &mut world,
+        };
+        toggle_fullscreen(&input_system.pressed, &mut context);
+
+        if title_options_open {
+            let (lines, action) = run_options_menu("Options", &mut options_menu, &input_system.pressed, &mut context);
+            match action {
+                MenuAction::Stay => {}
+                MenuAction::Close => title_options_open = false,
+                MenuAction::Quit => return Ok(()),
+            }
+            text_screen.set_lines(&lines, &font, &mut rendering_system.texture_manager).map_err(GameError::rendering)?;
+            rendering_system.draw_text_screen(&text_screen.textures());
+            continue;
+        }
+
+        if input_system.pressed.contains(&Keycode::Escape) {
+            return Ok(());
+        }
+        if input_system.pressed.contains(&Keycode::O) {
+            title_options_open = true;
+        }
+        if input_system.pressed.contains(&Keycode::Return) {
+            break;
+        }
+
+        if let Some(ref mut leaderboard) = leaderboard {
+            leaderboard.update();
+        }
+
+        text_screen.set_lines(
+            &screens::title_lines(&highscores, leaderboard.as_ref().map(|leaderboard| &leaderboard.top)),
+            &font,
+            &mut rendering_system.texture_manager
+        ).map_err(GameError::rendering)?;
+        rendering_system.draw_text_screen(&text_screen.textures());
+    }
+
+    let default_weights = ball_info.iter()
+        .map(|info| (info.ball_type, info.weight))
+        .collect::<Vec<_>>();
+    let waves_path = assets.config("waves").map_err(&manifest_error)?;
+    let waves = director::load_waves(&waves_path, &default_weights, &assets).map_err(GameError::asset(&waves_path))?;
+    let mut director = Director::new(waves);
+    let lives = health::total_lives(&world);
+    director.update(&mut world, time::precise_time_s() as f32, 0, lives);
 
     for _ in 0..20 {
-        ball_spawner.spawn_ball(&mut world);
+        ball_spawner.try_spawn_ball(&mut world);
     }
 
     let start_time = time::precise_time_s() as f32;
-    let music = sdl2::mixer::Music::from_file("data/music.ogg").unwrap();
-    music.play(-1).unwrap();
+    let seed = rand::random::<u32>();
+    let mut music_director = if audio.is_silent() {
+        None
+    } else {
+        let tracks = assets.config("music").and_then(|path| music::load_tracks(&path, &assets));
+        match tracks.and_then(|tracks| MusicDirector::new(tracks, &assets)) {
+            Ok(music_director) => Some(music_director),
+            // The game goes on without music
+            Err(e) => {
+                println!("Could not load the music: {}", e);
+                None
+            }
+        }
+    };
 
-    world.add_resource(HitBad(false));
-    world.add_resource(HitNeutral(false));
-    world.add_resource(HitGood(false));
-    world.add_resource(ScreenShake(None));
+    // `--arena circle` or `--arena rectangle` enables bouncy arena rules
+    let arena = args.iter()
+        .position(|arg| arg == "--arena")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|name| Arena::from_name(name))
+        .unwrap_or_default();
+    world.add_resource(arena);
+
+    // Length of the game if it ended with the player dying
+    let mut game_over = None;
+
+    // The game stands still while the options menu is open
+    let mut paused = false;
+    let mut paused_time = 0.;
+
+    let mut watcher = if dev_mode { Some(FileWatcher::new(&assets.files())) } else { None };
+
+    // Text that is only shown during the game is unloaded when it ends
+    let game_scope = rendering_system.texture_manager.begin_scope();
+    let score_texture_id = rendering_system.texture_manager.make_text_texture(
+        "Score: 0", &font, None
+    ).map_err(GameError::rendering)?;
+    create_text_entity(score_texture_id, &mut world);
+    // The "+N" texts are cached since there are only a few different ones
+    let mut popup_textures = HashMap::new();
 
     let mut old_time = 0.0;
     'running: loop {
-        let curr_time = time::precise_time_s() as f32;
-        let frametime = curr_time - old_time;
-        old_time = curr_time;
+        let real_time = time::precise_time_s() as f32;
+        let frametime = real_time - old_time;
+        old_time = real_time;
+
+        let changed = watcher.as_mut().map_or(vec!(), |watcher| watcher.poll(real_time));
+        for path in changed {
+            let result = if path == balls_path {
+                // Sprites and spawn weights are only used when the game starts
+                powerup::load_ball_info(&path, &assets).map(|ball_info| {
+                    powerup_system.durations = powerup::durations(&ball_info);
+                    ball_sound_system.sounds = powerup::collect_sounds(&ball_info);
+                    spatial_audio_system.emitters = powerup::emitters(&ball_info);
+                    true
+                })
+            } else if path == movement_path {
+                movement::load_movement(&path, &assets).map(|movement| {
+                    world.write::<MaxVelocity>().insert(player_entity, MaxVelocity(movement.top_speed));
+                    world.write::<Movement>().insert(player_entity, movement);
+                    true
+                })
+            } else if path == waves_path {
+                director::load_waves(&path, &default_weights, &assets).map(|waves| {
+                    director.set_waves(waves);
+                    true
+                })
+            } else if path == sounds_path {
+                audio::load_sounds(&path, &assets).map(|sound_list| {
+                    audio.set_sound_list(sound_list);
+                    true
+                })
+            } else {
+                let texture = rendering_system.texture_manager.reload(&path);
+                let sound = audio.reload(&path);
+                texture.and_then(|texture| sound.map(|sound| texture || sound))
+            };
+            match result {
+                Ok(true) => println!("Reloaded {}", path),
+                Ok(false) => println!("{} changed, restart the game to see the changes", path),
+                Err(e) => println!("Could not reload {}: {}", path, e),
+            }
+        }
+
+        if paused {
+            paused_time += frametime;
+            if let Err(e) = audio.update_loops(&[]) {
+                println!("Could not stop looping sounds: {}", e);
+            }
+            input_system.run_now(&world.res);
+            if input_system.should_exit {
+                break 'running;
+            }
+            let mut context = SettingsContext {
+                settings: &mut settings,
+                settings_file: &settings_file,
+                rendering: &mut rendering_system,
+                audio: &mut audio,
+                world: &mut world,
+            };
+            toggle_fullscreen(&input_system.pressed, &mut context);
+
+            let (lines, action) = run_options_menu("Paused", &mut options_menu, &input_system.pressed, &mut context);
+            match action {
+                MenuAction::Stay => {}
+                MenuAction::Close => paused = false,
+                MenuAction::Quit => break 'running,
+            }
+            text_screen.set_lines(&lines, &font, &mut rendering_system.texture_manager).map_err(GameError::rendering)?;
+            rendering_system.draw_text_screen(&text_screen.textures());
+            continue;
+        }
+
+        // Time in the game, which doesn't count the time spent paused
+        let curr_time = real_time - paused_time;
         motion_system.frametime = frametime;
+        lifetime_system.frametime = frametime;
+        powerup_system.frametime = frametime;
+        score_system.frametime = frametime;
+        health_system.frametime = frametime;
+        dash_system.frametime = frametime;
+        movement_system.frametime = frametime;
+        camera_system.frametime = frametime;
+        shake_system.frametime = frametime;
+        spatial_audio_system.frametime = frametime;
 
         obama_system.run_now(&world.res);
         motion_system.run_now(&world.res);
+        physics_system.run_now(&world.res);
         obama_system.run_now(&world.res);
         input_system.run_now(&world.res);
+        if input_system.pressed.contains(&Keycode::Escape) {
+            paused = true;
+        }
+        let mut context = SettingsContext {
+            settings: &mut settings,
+            settings_file: &settings_file,
+            rendering: &mut rendering_system,
+            audio: &mut audio,
+            world: &mut world,
+        };
+        toggle_fullscreen(&input_system.pressed, &mut context);
+        dash_system.run_now(&world.res);
+        movement_system.run_now(&world.res);
         collision_system.run_now(&world.res);
+        health_system.run_now(&world.res);
+        powerup_system.run_now(&world.res);
+        score_system.run_now(&world.res);
+        ball_sound_system.run_now(&world.res);
+        lifetime_system.run_now(&world.res);
         orbit_system.run_now(&world.res);
         max_vel_system.run_now(&world.res);
+        bounds_system.run_now(&world.res);
+        camera_system.run_now(&world.res);
+        shake_system.run_now(&world.res);
+        spatial_audio_system.run_now(&world.res);
         respawn_system.run_now(&world.res);
 
         world.maintain();
@@ -263,75 +777,207 @@ pub fn main() {
             game::create_obama(&mut world, &obama_sprites);
         }
 
-        ball_spawner.do_spawn(&mut world);
+        ball_spawner.do_spawn(&mut world, curr_time);
 
-        //Handle points and endgame
-        let new_points = collision_system.new_points;
-        points += new_points;
+        let points = world.read_resource::<Score>().points;
+        let life = health::total_lives(&world);
+
+        for event in director.update(&mut world, curr_time, points, life) {
+            match event {
+                WaveEvent::BallBurst(amount) => for _ in 0..amount {
+                    ball_spawner.try_spawn_ball(&mut world);
+                },
+                WaveEvent::ObamaSwarm(amount) => for _ in 0..amount {
+                    game::create_obama(&mut world, &obama_sprites);
+                },
+            }
+        }
+
+        // Floating text for the points scored this frame
+        let popups = world.write_resource::<ScorePopups>().0.drain(..).collect::<Vec<_>>();
+        for (pos, amount) in popups {
+            let texture = match popup_textures.get(&amount).cloned() {
+                Some(texture) => texture,
+                None => {
+                    let texture = rendering_system.texture_manager
+                        .make_text_texture(&format!("{:+}", amount), &font, None)
+                        .map_err(GameError::rendering)?;
+                    popup_textures.insert(amount, texture);
+                    texture
+                }
+            };
+            create_popup_entity(texture, pos, &mut world);
+        }
 
+        //Handle points and endgame
         // TODO: this could be optimized to only create a new texture when the text changes
-        let score_string = format!(
-            "Score: {} Life: {} Sausage countdown: {}",
-            points,
-            life,
-            (curr_time - start_time) as i32 - 180
-        );
+        let score_string = {
+            let score = world.read_resource::<Score>();
+            format!(
+                "Score: {} x{:.1} Combo: {} Life: {} Wave: {} Sausage countdown: {} {}",
+                score.points,
+                score.multiplier,
+                score.combo,
+                life,
+                director.current_wave().map(|wave| wave.name.as_str()).unwrap_or(""),
+                -script_runner.time_until_spawn("sausage", curr_time - start_time).unwrap_or(0.) as i32,
+                world.read_resource::<ActiveEffects>().describe()
+            )
+        };
         rendering_system.texture_manager.make_text_texture(
             &score_string, &font, Some(score_texture_id)
-        ).unwrap();
+        ).map_err(GameError::rendering)?;
 
-        {
-            let mut hit_bad = world.write_resource::<HitBad>();
-            if hit_bad.0 {
-                life -= 1;
-                *hit_bad = HitBad(false);
-            }
-            if life < 0 {
-                println!("You died, final score: {}", points);
-                break 'running;
-            }
+        if health::all_players_gone(&world) {
+            println!("You died, final score: {}", points);
+            game_over = Some(curr_time - start_time);
+            break 'running;
         }
 
         if input_system.should_exit {
             break 'running;
         }
 
-        // TODO: everything below this point could probably be moved into a new system
-        let nuke_time = 180.0;
-        let nuke_angle = orbit_system.nuke_angle;
+        let script_context = ScriptContext {
+            elapsed: curr_time - start_time,
+            points,
+            life,
+        };
+        for command in script_runner.update(&mut world, &script_context) {
+            match command {
+                ScriptCommand::PlaySound(file) => world.write_resource::<SoundQueue>().0.push(file),
+                ScriptCommand::ChangeMusic(track) => if let Some(ref mut music_director) = music_director {
+                    music_director.force(track);
+                },
+            }
+        }
 
-        if curr_time - start_time > nuke_time && sausage_is_spawned == false {
-            sausage_is_spawned = true;
+        let music_state = if script_runner.is_alive(&world, "sausage") {
+            MusicState::Boss
+        } else if life <= 1 {
+            MusicState::Intense
+        } else {
+            MusicState::Calm
+        };
+        if let Some(ref mut music_director) = music_director {
+            match music_director.update(music_state, real_time, audio.group_volume(SoundGroup::Music)) {
+                Ok(beat) => *world.write_resource::<MusicBeat>() = beat,
+                Err(e) => println!("Could not play the music: {}", e),
+            }
+        }
 
-            let sausage_sprite = sausage_sprite;
-            world.create_entity()
-                .with(sausage_transform)
-                .with(OrbitComponent{radius: 1000., target_radius:150., angle:0., angular_velocity: 0.02})
-                .with(sausage_sprite)
-                .build();
+        let sounds = world.write_resource::<SoundQueue>().0.drain(..).collect::<Vec<_>>();
+        for sound in sounds {
+            if let Err(e) = audio.play(&sound) {
+                println!("Could not play {}: {}", sound, e);
+            }
         }
 
-        if curr_time - start_time > nuke_time + 10. {
-            {
-                let mut screen_shake = world.write_resource::<ScreenShake>();
-                *screen_shake = ScreenShake(Some(10.));
+        let emitted = world.read_resource::<EmittedSounds>();
+        for &(ref sound, position) in &emitted.once {
+            if let Err(e) = audio.play_at(sound, Some(position)) {
+                println!("Could not play {}: {}", sound, e);
             }
-            if !nuke_is_spawned {
-                nuke_is_spawned = true;
-
-                for _ in 0..3 {
-                    let bound = components::BoundingCircle { radius: 28.0 * 0.5 };
-                    let ball_type = BallType::Bad;
-
-                    world.create_entity()
-                        .with(nuke_transform)
-                        .with(OrbitComponent{radius: 250., target_radius:0., angle:nuke_angle, angular_velocity: 0.02})
-                        .with(nuke_sprite)
-                        .with(bound)
-                        .with(ball_type.clone())
-                        .build();
-                }
+        }
+        if let Err(e) = audio.update_loops(&emitted.loops) {
+            println!("Could not play looping sounds: {}", e);
+        }
+    }
+
+    if let Err(e) = audio.update_loops(&[]) {
+        println!("Could not stop looping sounds: {}", e);
+    }
+
+    // The text screen may have made textures for the options menu in the scope
+    text_screen.clear(&mut rendering_system.texture_manager);
+    rendering_system.texture_manager.end_scope(game_scope);
+    if dev_mode {
+        println!("Textures after the game: {}", rendering_system.texture_manager.stats());
+    }
+
+    let duration = match game_over {
+        Some(duration) => duration,
+        None => return Ok(()),
+    };
+    let (points, max_combo) = {
+        let score = world.read_resource::<Score>();
+        (score.points, score.max_combo)
+    };
+
+    let mut score = HighScore {
+        name: highscore::clean_name(""),
+        points,
+        date: highscore::now(),
+        seed,
+        duration,
+        max_combo,
+    };
+
+    let mut rank = highscores.rank_of(points);
+    if rank.is_some() {
+        // Name entry, leaving without entering a name still saves the score
+        let mut name = String::new();
+        video_subsystem.text_input().start();
+        loop {
+            input_system.run_now(&world.res);
+            let mut context = SettingsContext {
+                settings: &mut settings,
+                settings_file: &settings_file,
+                rendering: &mut rendering_system,
+                audio: &mut audio,
+                world: &mut world,
+            };
+            toggle_fullscreen(&input_system.pressed, &mut context);
+            name.push_str(&input_system.text);
+            if input_system.pressed.contains(&Keycode::Backspace) {
+                name.pop();
+            }
+            name = name.chars().take(highscore::MAX_NAME_LENGTH).collect();
+
+            let pressed = &input_system.pressed;
+            if input_system.should_exit || pressed.contains(&Keycode::Return) || pressed.contains(&Keycode::Escape) {
+                break;
             }
+
+            text_screen.set_lines(
+                &screens::name_entry_lines(points, rank.unwrap(), &name),
+                &font,
+                &mut rendering_system.texture_manager
+            ).map_err(GameError::rendering)?;
+            rendering_system.draw_text_screen(&text_screen.textures());
+        }
+        video_subsystem.text_input().stop();
+
+        score.name = highscore::clean_name(&name);
+        rank = highscores.insert(score.clone());
+        if let Err(e) = highscores.save() {
+            println!("Could not save high scores: {}", e);
+        }
+    }
+
+    if let Some(ref leaderboard) = leaderboard {
+        leaderboard.submit(Submission::new(score));
+    }
+
+    while !input_system.should_exit {
+        input_system.run_now(&world.res);
+        let mut context = SettingsContext {
+            settings: &mut settings,
+            settings_file: &settings_file,
+            rendering: &mut rendering_system,
+            audio: &mut audio,
+            world: &mut world,
+        };
+        toggle_fullscreen(&input_system.pressed, &mut context);
+        if input_system.pressed.contains(&Keycode::Return) || input_system.pressed.contains(&Keycode::Escape) {
+            break;
         }
+
+        text_screen.set_lines(
+            &screens::game_over_lines(&highscores, points, rank), &font, &mut rendering_system.texture_manager
+        ).map_err(GameError::rendering)?;
+        rendering_system.draw_text_screen(&text_screen.textures());
     }
+
+    Ok(())
 }
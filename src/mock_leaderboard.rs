@@ -0,0 +1,81 @@
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use config;
+use highscore::HighScore;
+use http;
+use leaderboard::Submission;
+
+// Most scores that a single request may ask for
+const MAX_COUNT: usize = 100;
+
+/// Starts a leaderboard server on a thread of the game and returns its url.
+/// It answers like a real server would but keeps the scores in memory, so the
+/// online leaderboard can be tried out without one
+pub fn start() -> Result<String, String> {
+    let listener = TcpListener::bind("127.0.0.1:0").map_err(|e| e.to_string())?;
+    let url = format!("http://{}", listener.local_addr().map_err(|e| e.to_string())?);
+    let scores = Arc::new(Mutex::new(vec!()));
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.map_err(|e| e.to_string())
+                .and_then(|stream| handle(stream, &scores));
+            if let Err(e) = result {
+                println!("Mock leaderboard: {}", e);
+            }
+        }
+    });
+
+    println!("Mock leaderboard running at {}", url);
+    Ok(url)
+}
+
+fn handle(stream: TcpStream, scores: &Mutex<Vec<HighScore>>) -> Result<(), String> {
+    let request = http::read_message(&stream)?;
+    let mut words = request.start_line.split_whitespace();
+    let method = words.next().unwrap_or("");
+    let target = words.next().unwrap_or("");
+    let (path, query) = match target.find('?') {
+        Some(i) => (&target[..i], &target[i + 1..]),
+        None => (target, ""),
+    };
+
+    let (status, body) = match (method, path) {
+        ("GET", "/scores") => {
+            let count = query.split('&')
+                .find(|param| param.starts_with("count="))
+                .and_then(|param| param["count=".len()..].parse::<usize>().ok())
+                .unwrap_or(10)
+                .min(MAX_COUNT);
+            let scores = scores.lock().unwrap();
+            let sections = scores.iter().take(count).map(HighScore::to_section).collect::<Vec<_>>();
+            ("200 OK", config::format(&sections))
+        }
+        ("POST", "/scores") => match add_scores(&request.body, scores) {
+            Ok(()) => ("200 OK", String::new()),
+            Err(e) => ("400 Bad Request", e),
+        },
+        _ => ("404 Not Found", format!("no such page: {} {}", method, path)),
+    };
+
+    http::write_message(&stream, &format!("HTTP/1.0 {}", status), &[], &body)
+}
+
+fn add_scores(body: &str, scores: &Mutex<Vec<HighScore>>) -> Result<(), String> {
+    let submissions = config::parse(body)?.iter()
+        .filter(|section| section.name == "score")
+        .map(Submission::from_section)
+        .collect::<Result<Vec<_>, _>>()?;
+    // Only catches scores that got garbled, there is nothing to check them
+    // against yet
+    if submissions.iter().any(|submission| !submission.checksum_matches()) {
+        return Err("checksum does not match the score".to_string());
+    }
+
+    let mut scores = scores.lock().unwrap();
+    scores.extend(submissions.into_iter().map(|submission| submission.score));
+    scores.sort_by(|a, b| b.points.cmp(&a.points).then(a.date.cmp(&b.date)));
+    Ok(())
+}
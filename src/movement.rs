@@ -0,0 +1,123 @@
+extern crate specs;
+
+use nalgebra::{Vector2, Dot, Norm};
+
+use specs::{Join, VecStorage};
+
+use assets::Assets;
+use components::Velocity;
+use config::Section;
+use dash::Dash;
+use player::{self, PlayerComponent};
+
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+#[component(VecStorage)]
+/// How a player speeds up and slows down. All values are per second
+pub struct Movement {
+    // Speed gained per second while a direction is held
+    pub acceleration: f32,
+    // Fraction of the speed lost per second, always applied
+    pub drag: f32,
+    // Speed lost per second when no direction is held or when steering against
+    // the current motion
+    pub braking: f32,
+    // Holding a direction doesn't accelerate past this speed
+    pub top_speed: f32,
+}
+
+impl Movement {
+    fn from_section(section: &Section) -> Result<Movement, String> {
+        Ok(Movement {
+            acceleration: section.get_or("acceleration", 900.)?,
+            drag: section.get_or("drag", 0.)?,
+            braking: section.get_or("braking", 0.)?,
+            top_speed: section.get_or("top_speed", 100.)?,
+        })
+    }
+}
+
+/// Loads the movement preset selected by the `[movement]` section of `path`
+pub fn load_movement(path: &str, assets: &Assets) -> Result<Movement, String> {
+    let sections = assets.load_config(path)?;
+
+    let preset = sections.iter()
+        .find(|section| section.name == "movement")
+        .and_then(|section| section.get_str("preset"))
+        .ok_or_else(|| format!("{}: no movement preset selected", path))?;
+
+    sections.iter()
+        .filter(|section| section.name == "preset")
+        .find(|section| section.get_str("name") == Some(preset))
+        .ok_or_else(|| format!("unknown movement preset {}", preset))
+        .and_then(Movement::from_section)
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+fn input_direction(player: &PlayerComponent) -> Vector2<f32> {
+    let mut direction = Vector2::new(0., 0.);
+    if player.get_key(player::Keys::Up) {
+        direction.y -= 1.;
+    }
+    if player.get_key(player::Keys::Down) {
+        direction.y += 1.;
+    }
+    if player.get_key(player::Keys::Right) {
+        direction.x += 1.;
+    }
+    if player.get_key(player::Keys::Left) {
+        direction.x -= 1.;
+    }
+
+    if direction.norm_squared() > 0. {
+        direction.normalize()
+    } else {
+        direction
+    }
+}
+
+/// Turns the held direction keys into player velocity
+pub struct MovementSystem {
+    pub frametime: f32,
+}
+
+impl<'a> specs::System<'a> for MovementSystem {
+    type SystemData = (
+        specs::Entities<'a>,
+        specs::WriteStorage<'a, Velocity>,
+        specs::ReadStorage<'a, Movement>,
+        specs::ReadStorage<'a, PlayerComponent>,
+        specs::ReadStorage<'a, Dash>,
+    );
+    fn run(&mut self, (entities, mut velocities, movements, players, dashes): Self::SystemData) {
+        for (entity, velocity, movement, player) in
+            (&*entities, &mut velocities, &movements, &players).join()
+        {
+            // Dashes keep their speed until they are over
+            if dashes.get(entity).map(|dash| dash.is_dashing()).unwrap_or(false) {
+                continue;
+            }
+
+            let direction = input_direction(player);
+
+            if direction.norm_squared() > 0. {
+                // Cancel motion against the held direction quicker than drag would
+                let along = velocity.0.dot(&direction);
+                if along < 0. {
+                    velocity.0 += direction * (-along).min(movement.braking * self.frametime);
+                }
+
+                if velocity.0.dot(&direction) < movement.top_speed {
+                    velocity.0 += direction * movement.acceleration * self.frametime;
+                }
+            } else {
+                let speed = velocity.0.norm();
+                if speed > 0. {
+                    let new_speed = (speed - movement.braking * self.frametime).max(0.);
+                    velocity.0 = velocity.0 * (new_speed / speed);
+                }
+            }
+
+            velocity.0 = velocity.0 * (1. - movement.drag * self.frametime).max(0.);
+        }
+    }
+}
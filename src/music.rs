@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use sdl2::mixer::{self, Channel, Chunk};
+
+use assets::Assets;
+use audio::{self, SoundGroup};
+
+// Seconds that a crossfade between two tracks takes
+const CROSSFADE_TIME: f32 = 2.;
+// Tracks don't wait longer than this for a beat to change on
+const MAX_BEAT_WAIT: f32 = 1.;
+
+/// What the game is like right now, each state has its own track
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MusicState {
+    Calm,
+    // The player is about to run out of lives
+    Intense,
+    // The sausage is out
+    Boss,
+}
+
+impl MusicState {
+    pub fn all() -> [MusicState; 3] {
+        [MusicState::Calm, MusicState::Intense, MusicState::Boss]
+    }
+
+    pub fn from_name(name: &str) -> Option<MusicState> {
+        MusicState::all().iter().cloned().find(|state| state.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            MusicState::Calm => "calm",
+            MusicState::Intense => "intense",
+            MusicState::Boss => "boss",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrackInfo {
+    pub state: MusicState,
+    pub file: String,
+    pub bpm: f32,
+}
+
+pub fn load_tracks(path: &str, assets: &Assets) -> Result<Vec<TrackInfo>, String> {
+    assets.load_config(path)?.iter()
+        .filter(|section| section.name == "track")
+        .map(|section| {
+            let state = section.get_str("state").ok_or("track without a state")?;
+            Ok(TrackInfo {
+                state: MusicState::from_name(state).ok_or_else(|| format!("unknown music state {}", state))?,
+                file: assets.sound(section.get_str("asset").ok_or_else(|| format!("{} track has no asset", state))?)?,
+                bpm: section.get_or("bpm", 120.)?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Resource with the tempo of the music that is playing, for effects that pulse
+/// along with it
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MusicBeat {
+    pub bpm: f32,
+    // Beats since the track started, counting the fraction of the current one
+    pub beat: f32,
+}
+
+impl MusicBeat {
+    pub fn none() -> MusicBeat {
+        MusicBeat { bpm: 0., beat: 0. }
+    }
+
+    /// How far into the current beat the music is, between 0 and 1
+    pub fn phase(&self) -> f32 {
+        self.beat - self.beat.floor()
+    }
+
+    /// 1 right on the beat, falling off quickly to 0 before the next one
+    pub fn pulse(&self) -> f32 {
+        if self.bpm <= 0. {
+            return 0.;
+        }
+        (1. - self.phase()).powi(4)
+    }
+}
+
+struct Playing {
+    state: MusicState,
+    channel: Channel,
+    started: f32,
+    // Goes from 0 to 1 while fading in, and back to 0 while fading out
+    fade: f32,
+}
+
+/// Plays the track that fits the state of the game and crossfades to another one
+/// when the state changes. Changes wait for the next beat so that the new track
+/// comes in on the beat of the old one.
+pub struct MusicDirector {
+    tracks: HashMap<MusicState, (TrackInfo, Chunk)>,
+    playing: Vec<Playing>,
+    // Scripts can pick a track themselves instead of leaving it to the game state
+    forced: Option<MusicState>,
+    // State to switch to and when
+    pending: Option<(MusicState, f32)>,
+    last_update: Option<f32>,
+}
+
+impl MusicDirector {
+    pub fn new(tracks: Vec<TrackInfo>, assets: &Assets) -> Result<MusicDirector, String> {
+        let mut loaded = HashMap::new();
+        for info in tracks {
+            let chunk = audio::chunk_from_bytes(&assets.read(&info.file)?).map_err(|e| format!("{}: {}", info.file, e))?;
+            loaded.insert(info.state, (info, chunk));
+        }
+
+        if !loaded.contains_key(&MusicState::Calm) {
+            return Err("there is no calm music track".to_string());
+        }
+
+        Ok(MusicDirector {
+            tracks: loaded,
+            playing: vec!(),
+            forced: None,
+            pending: None,
+            last_update: None,
+        })
+    }
+
+    /// Plays `state` no matter what the game is doing, or goes back to following
+    /// the game with None
+    pub fn force(&mut self, state: Option<MusicState>) {
+        self.forced = state;
+    }
+
+    fn current(&self) -> Option<&Playing> {
+        self.playing.last()
+    }
+
+    fn beat_of(&self, playing: &Playing, curr_time: f32) -> MusicBeat {
+        let bpm = self.tracks[&playing.state].0.bpm;
+        MusicBeat {
+            bpm,
+            beat: (curr_time - playing.started) * bpm / 60.,
+        }
+    }
+
+    /// Time of the next beat of the current track, or now if that is too far off
+    fn next_beat(&self, curr_time: f32) -> f32 {
+        match self.current() {
+            Some(playing) => {
+                let beat = self.beat_of(playing, curr_time);
+                let wait = (1. - beat.phase()) * 60. / beat.bpm;
+                if wait <= MAX_BEAT_WAIT { curr_time + wait } else { curr_time }
+            }
+            None => curr_time,
+        }
+    }
+
+    fn start(&mut self, state: MusicState, curr_time: f32) -> Result<(), String> {
+        let channel = match SoundGroup::Music.mixer_group().find_available() {
+            Some(channel) => channel,
+            // Both channels are still busy with a crossfade, cut the oldest one
+            None if !self.playing.is_empty() => {
+                let oldest = self.playing.remove(0);
+                oldest.channel.halt();
+                oldest.channel
+            }
+            // Something else holds the music channels. The switch is skipped
+            // and tried again later
+            None => return Ok(()),
+        };
+
+        let fade = if self.playing.is_empty() { 1. } else { 0. };
+        channel.set_volume(0);
+        channel.play(&self.tracks[&state].1, -1)?;
+        self.playing.push(Playing { state, channel, started: curr_time, fade });
+        Ok(())
+    }
+
+    /// Follows `wanted` unless a script forces a track, fades tracks in and out
+    /// and returns the beat of the music that is playing
+    pub fn update(&mut self, wanted: MusicState, curr_time: f32, volume: f32) -> Result<MusicBeat, String> {
+        let frametime = curr_time - self.last_update.unwrap_or(curr_time);
+        self.last_update = Some(curr_time);
+
+        let mut target = self.forced.unwrap_or(wanted);
+        if !self.tracks.contains_key(&target) {
+            target = MusicState::Calm;
+        }
+
+        let current_state = self.current().map(|playing| playing.state);
+        if current_state != Some(target) {
+            let pending_state = self.pending.map(|(state, _)| state);
+            if pending_state != Some(target) {
+                self.pending = Some((target, self.next_beat(curr_time)));
+            }
+        } else {
+            self.pending = None;
+        }
+
+        if let Some((state, at)) = self.pending {
+            if curr_time >= at {
+                self.pending = None;
+                self.start(state, curr_time)?;
+            }
+        }
+
+        // The newest track fades in, all others fade out and stop once silent
+        let fade_step = frametime / CROSSFADE_TIME;
+        let newest = self.playing.len().saturating_sub(1);
+        for (i, playing) in self.playing.iter_mut().enumerate() {
+            playing.fade = if i == newest {
+                (playing.fade + fade_step).min(1.)
+            } else {
+                (playing.fade - fade_step).max(0.)
+            };
+            playing.channel.set_volume((playing.fade * volume * mixer::MAX_VOLUME as f32) as i32);
+        }
+        for playing in self.playing.iter().filter(|playing| playing.fade <= 0.) {
+            playing.channel.halt();
+        }
+        self.playing.retain(|playing| playing.fade > 0.);
+
+        Ok(match self.current() {
+            Some(playing) => self.beat_of(playing, curr_time),
+            None => MusicBeat::none(),
+        })
+    }
+}
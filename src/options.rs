@@ -0,0 +1,93 @@
+use sdl2::keyboard::Keycode;
+
+use audio::SoundGroup;
+use cone::ConeMode;
+use player::Keys;
+use settings::{self, Settings};
+use ui::{Ui, UiInput, UiState};
+use viewport::{ScaleMode, WindowMode};
+
+/// What the player chose to do in the options menu
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MenuAction {
+    Stay,
+    Close,
+    Quit,
+}
+
+/// The options menu, shown over the title screen or when the game is paused
+pub struct OptionsMenu {
+    ui: UiState,
+    // Action that the next key press gets bound to
+    rebinding: Option<Keys>,
+}
+
+impl OptionsMenu {
+    pub fn new() -> OptionsMenu {
+        OptionsMenu { ui: UiState::default(), rebinding: None }
+    }
+
+    /// Runs the menu for a frame with the keys that were pressed. Returns the
+    /// lines to show under `title` and what to do next. `settings` is changed
+    /// in place
+    pub fn update(&mut self, title: &str, settings: &mut Settings, pressed: &[Keycode]) -> (Vec<String>, MenuAction) {
+        // While waiting for a key, all keys go to the binding
+        let mut input = UiInput::from_keys(pressed);
+        if let Some(action) = self.rebinding {
+            if let Some(&keycode) = pressed.first() {
+                if keycode != Keycode::Escape {
+                    settings.bind(action, keycode);
+                }
+                self.rebinding = None;
+            }
+            input = UiInput::default();
+        }
+
+        let mut ui = Ui::new(&mut self.ui, input);
+        let mut action = if ui.back_pressed() { MenuAction::Close } else { MenuAction::Stay };
+
+        ui.label(title);
+        ui.label("");
+        if ui.button("Back") {
+            action = MenuAction::Close;
+        }
+
+        ui.slider("Volume", &mut settings.master_volume, 1., 0.1);
+        for &(group, name) in &[(SoundGroup::Sfx, "Effects"), (SoundGroup::Ui, "Interface"), (SoundGroup::Music, "Music")] {
+            let mut volume = settings.volume(group);
+            if ui.slider(name, &mut volume, 1., 0.1) {
+                settings.volumes.insert(group, volume);
+            }
+        }
+
+        let scales = (1..settings::MAX_SCALE + 1).collect::<Vec<_>>();
+        ui.choice("Window size", &mut settings.scale, &scales, |scale| format!("x{}", scale));
+        ui.choice("Window", &mut settings.window_mode, &WindowMode::all(), |mode| mode.name().to_string());
+        ui.choice("Scaling", &mut settings.scale_mode, &ScaleMode::all(), |mode| mode.name().to_string());
+        ui.toggle("Vsync (after restart)", &mut settings.vsync);
+        ui.slider("Screen shake", &mut settings.shake, 2., 0.25);
+        ui.choice("Aim", &mut settings.cone_mode, &ConeMode::all(), |mode| mode.name().to_string());
+
+        ui.label("");
+        for &key in &Keys::all() {
+            let bound = if self.rebinding == Some(key) {
+                "press a key".to_string()
+            } else {
+                settings.bindings.get(&key).map(|keycode| keycode.name()).unwrap_or_default()
+            };
+            if ui.button(&format!("{}: {}", key.name(), bound)) {
+                self.rebinding = Some(key);
+            }
+        }
+
+        ui.label("");
+        if ui.button("Reset to defaults") {
+            *settings = Settings::default();
+        }
+        if ui.button("Quit game") {
+            action = MenuAction::Quit;
+        }
+
+        (ui.finish(), action)
+    }
+}
@@ -0,0 +1,37 @@
+use std::env;
+use std::path::PathBuf;
+
+const APP_DIR: &str = "nihil-chroma";
+
+/// `$<variable>/nihil-chroma`, or `~/<fallback>/nihil-chroma` if the variable
+/// isn't set, following the XDG base directory spec
+fn xdg_dir(variable: &str, fallback: &str) -> Result<PathBuf, String> {
+    let base = env::var_os(variable)
+        .map(PathBuf::from)
+        // Relative paths are invalid according to the spec and should be ignored
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+        .ok_or_else(|| format!("neither {} nor HOME is set", variable))?;
+    Ok(base.join(APP_DIR))
+}
+
+/// Directory for things the game keeps track of, like high scores
+pub fn data_dir() -> Result<PathBuf, String> {
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Directory for the settings that the player picks
+pub fn config_dir() -> Result<PathBuf, String> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// Directories with files installed for all users, from `$XDG_DATA_DIRS`
+pub fn system_data_dirs() -> Vec<PathBuf> {
+    let dirs = env::var_os("XDG_DATA_DIRS")
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+    env::split_paths(&dirs)
+        .filter(|path| path.is_absolute())
+        .map(|path| path.join(APP_DIR))
+        .collect()
+}
@@ -0,0 +1,221 @@
+extern crate specs;
+
+use nalgebra::{Vector2, Dot, Norm};
+
+use specs::Join;
+
+use components::{Transform, Velocity, BoundingCircle, RigidBody};
+use constants::*;
+
+/// The shape of the play area that balls bounce off
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ArenaShape {
+    Circle { center: Vector2<f32>, radius: f32 },
+    Rectangle { min: Vector2<f32>, max: Vector2<f32> },
+}
+
+impl ArenaShape {
+    /// A circle inscribed in the world
+    pub fn world_circle() -> ArenaShape {
+        ArenaShape::Circle {
+            center: Vector2::new(WORLD_SIZE.0 as f32 / 2., WORLD_SIZE.1 as f32 / 2.),
+            radius: WORLD_SIZE.0.min(WORLD_SIZE.1) as f32 / 2.,
+        }
+    }
+
+    /// A rectangle covering the whole world
+    pub fn world_rectangle() -> ArenaShape {
+        ArenaShape::Rectangle {
+            min: Vector2::new(0., 0.),
+            max: Vector2::new(WORLD_SIZE.0 as f32, WORLD_SIZE.1 as f32),
+        }
+    }
+
+    pub fn contains(&self, pos: Vector2<f32>) -> bool {
+        match *self {
+            ArenaShape::Circle { center, radius } => {
+                (pos - center).norm_squared() < radius * radius
+            }
+            ArenaShape::Rectangle { min, max } => {
+                pos.x > min.x && pos.x < max.x && pos.y > min.y && pos.y < max.y
+            }
+        }
+    }
+
+    /// If a circle sticks out of the arena, returns the outward normal of the
+    /// wall it hit and how far into the wall it goes
+    pub fn wall_contact(&self, pos: Vector2<f32>, radius: f32) -> Option<(Vector2<f32>, f32)> {
+        match *self {
+            ArenaShape::Circle { center, radius: arena_radius } => {
+                let diff = pos - center;
+                let dist = diff.norm();
+                let penetration = dist + radius - arena_radius;
+                if penetration > 0. && dist > 0. {
+                    Some((diff / dist, penetration))
+                } else {
+                    None
+                }
+            }
+            ArenaShape::Rectangle { min, max } => {
+                // Pick the wall that the circle penetrates the deepest
+                let contacts = [
+                    (Vector2::new(-1., 0.), min.x - (pos.x - radius)),
+                    (Vector2::new(1., 0.), (pos.x + radius) - max.x),
+                    (Vector2::new(0., -1.), min.y - (pos.y - radius)),
+                    (Vector2::new(0., 1.), (pos.y + radius) - max.y),
+                ];
+
+                contacts.iter()
+                    .filter(|&&(_, penetration)| penetration > 0.)
+                    .fold(None, |deepest: Option<(Vector2<f32>, f32)>, &contact| {
+                        match deepest {
+                            Some(d) if d.1 >= contact.1 => Some(d),
+                            _ => Some(contact),
+                        }
+                    })
+            }
+        }
+    }
+}
+
+/// Resource describing the arena and which rigid body responses are active
+pub struct Arena {
+    pub shape: ArenaShape,
+    /// Reflect balls off the arena boundary instead of letting them leave
+    pub bouncy_walls: bool,
+    /// Make balls bounce off each other
+    pub ball_collisions: bool,
+}
+
+impl Arena {
+    /// Parses the arena rules from a command line value like `circle` or `rectangle`
+    pub fn from_name(name: &str) -> Option<Arena> {
+        match name {
+            "circle" => Some(Arena::bouncy(ArenaShape::world_circle())),
+            "rectangle" => Some(Arena::bouncy(ArenaShape::world_rectangle())),
+            _ => None,
+        }
+    }
+
+    /// Arena rules where balls stay inside `shape` and bounce off each other
+    pub fn bouncy(shape: ArenaShape) -> Arena {
+        Arena {
+            shape,
+            bouncy_walls: true,
+            ball_collisions: true,
+        }
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Arena {
+        Arena {
+            shape: ArenaShape::world_rectangle(),
+            bouncy_walls: false,
+            ball_collisions: false,
+        }
+    }
+}
+
+/// Bounces rigid bodies off each other and off the arena walls, if the arena
+/// enables it
+pub struct PhysicsSystem;
+
+fn resolve_ball_collision(
+    (pos1, vel1, radius1, body1): (Vector2<f32>, Vector2<f32>, f32, RigidBody),
+    (pos2, vel2, radius2, body2): (Vector2<f32>, Vector2<f32>, f32, RigidBody),
+) -> Option<((Vector2<f32>, Vector2<f32>), (Vector2<f32>, Vector2<f32>))>
+{
+    let diff = pos2 - pos1;
+    let dist = diff.norm();
+    let min_dist = radius1 + radius2;
+
+    if dist >= min_dist || dist == 0. {
+        return None;
+    }
+
+    let normal = diff / dist;
+    let inv_mass1 = 1. / body1.mass;
+    let inv_mass2 = 1. / body2.mass;
+    let inv_mass_sum = inv_mass1 + inv_mass2;
+
+    // Push the balls apart in proportion to their mass so they don't get stuck
+    let correction = normal * ((min_dist - dist) / inv_mass_sum);
+    let new_pos1 = pos1 - correction * inv_mass1;
+    let new_pos2 = pos2 + correction * inv_mass2;
+
+    let approach_speed = (vel2 - vel1).dot(&normal);
+    if approach_speed >= 0. {
+        // Already moving apart
+        return Some(((new_pos1, vel1), (new_pos2, vel2)));
+    }
+
+    let restitution = (body1.restitution + body2.restitution) / 2.;
+    let impulse = normal * (-(1. + restitution) * approach_speed / inv_mass_sum);
+
+    Some((
+        (new_pos1, vel1 - impulse * inv_mass1),
+        (new_pos2, vel2 + impulse * inv_mass2),
+    ))
+}
+
+impl<'a> specs::System<'a> for PhysicsSystem {
+    type SystemData = (
+        specs::Entities<'a>,
+        specs::WriteStorage<'a, Transform>,
+        specs::WriteStorage<'a, Velocity>,
+        specs::ReadStorage<'a, BoundingCircle>,
+        specs::ReadStorage<'a, RigidBody>,
+        specs::Fetch<'a, Arena>,
+    );
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut transforms, mut velocities, bounding_circles, bodies, arena) = data;
+
+        if arena.ball_collisions {
+            let balls = (&*entities, &transforms, &velocities, &bounding_circles, &bodies).join()
+                .map(|(entity, transform, velocity, circle, body)| {
+                    (entity, (transform.pos, velocity.0, circle.radius, *body))
+                })
+                .collect::<Vec<_>>();
+
+            let mut resolved = balls.iter().map(|&(_, ball)| ball).collect::<Vec<_>>();
+            for i in 0..resolved.len() {
+                for j in (i + 1)..resolved.len() {
+                    if let Some(((pos1, vel1), (pos2, vel2))) =
+                        resolve_ball_collision(resolved[i], resolved[j])
+                    {
+                        resolved[i].0 = pos1;
+                        resolved[i].1 = vel1;
+                        resolved[j].0 = pos2;
+                        resolved[j].1 = vel2;
+                    }
+                }
+            }
+
+            for (&(entity, _), &(pos, vel, _, _)) in balls.iter().zip(resolved.iter()) {
+                transforms.get_mut(entity).unwrap().pos = pos;
+                velocities.get_mut(entity).unwrap().0 = vel;
+            }
+        }
+
+        if arena.bouncy_walls {
+            for (transform, velocity, circle, body) in
+                (&mut transforms, &mut velocities, &bounding_circles, &bodies).join()
+            {
+                // Balls that are still on their way into the arena are left alone
+                if !arena.shape.contains(transform.pos) {
+                    continue;
+                }
+
+                if let Some((normal, penetration)) = arena.shape.wall_contact(transform.pos, circle.radius) {
+                    transform.pos -= normal * penetration;
+
+                    let outward_speed = velocity.0.dot(&normal);
+                    if outward_speed > 0. {
+                        velocity.0 -= normal * ((1. + body.restitution) * outward_speed);
+                    }
+                }
+            }
+        }
+    }
+}
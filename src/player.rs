@@ -1,13 +1,34 @@
 use specs::VecStorage;
 use std::collections::HashMap;
 
-#[derive(Eq, PartialEq, Hash)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Keys
 {
     Up,
     Down,
     Left,
     Right,
+    Dash,
+}
+
+impl Keys
+{
+    pub fn all() -> [Keys; 5]
+    {
+        [Keys::Up, Keys::Down, Keys::Left, Keys::Right, Keys::Dash]
+    }
+
+    pub fn name(&self) -> &'static str
+    {
+        match *self
+        {
+            Keys::Up => "up",
+            Keys::Down => "down",
+            Keys::Left => "left",
+            Keys::Right => "right",
+            Keys::Dash => "dash",
+        }
+    }
 }
 
 #[derive(Component)]
@@ -0,0 +1,262 @@
+extern crate specs;
+extern crate rand;
+
+use std::collections::HashMap;
+use std::f32::consts;
+
+use nalgebra::{Vector2, Norm};
+use rand::Rng;
+
+use specs::Join;
+
+use assets::Assets;
+use components::{Transform, Velocity, BallType, BoundingCircle, RigidBody, Lifetime};
+use components::TimeScale;
+use cone::ConeSize;
+use health::Health;
+use player::PlayerComponent;
+use spatial::SoundEmitter;
+use spawner::{BallEvent, BallEvents};
+use sprite::Sprite;
+
+const MAGNET_STRENGTH: f32 = 250.;
+const SLOW_TIME_SCALE: f32 = 0.4;
+const CONE_WIDENER_MULTIPLIER: f32 = 2.;
+const SPLITTER_SHARDS: u32 = 3;
+const SHARD_SPEED: f32 = 90.;
+const SHARD_LIFETIME: f32 = 6.;
+
+/// How a ball type looks and behaves, loaded from `data/balls.txt`
+#[derive(Clone, Debug)]
+pub struct BallInfo {
+    pub ball_type: BallType,
+    pub sprite: String,
+    // Default spawn weight for waves that don't mention the type
+    pub weight: f32,
+    // Seconds that the effect of the ball lasts, if it has a lasting effect
+    pub duration: f32,
+    // Sound played when the ball is collected
+    pub sound: Option<String>,
+    // Sound the ball keeps making, every `emit_interval` seconds or looping
+    pub emitter: Option<SoundEmitter>,
+}
+
+pub fn load_ball_info(path: &str, assets: &Assets) -> Result<Vec<BallInfo>, String> {
+    assets.load_config(path)?.iter()
+        .filter(|section| section.name == "ball")
+        .map(|section| {
+            let type_name = section.get_str("type").ok_or("ball without a type")?;
+            Ok(BallInfo {
+                ball_type: BallType::from_name(type_name)
+                    .ok_or_else(|| format!("unknown ball type {}", type_name))?,
+                sprite: section.get_str("sprite")
+                    .ok_or_else(|| format!("ball {} has no sprite", type_name))?
+                    .to_string(),
+                weight: section.get_or("weight", 0.)?,
+                duration: section.get_or("duration", 0.)?,
+                sound: section.get_str("sound").map(String::from),
+                emitter: match section.get_str("emit") {
+                    // Without an interval the sound loops
+                    Some(sound) => {
+                        let interval = section.get_or("emit_interval", 0.)?;
+                        let interval = if interval > 0. { Some(interval) } else { None };
+                        Some(SoundEmitter::new(sound.to_string(), interval))
+                    }
+                    None => None,
+                },
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// How long the effect of each ball type lasts
+pub fn durations(ball_info: &[BallInfo]) -> HashMap<BallType, f32> {
+    ball_info.iter().map(|info| (info.ball_type, info.duration)).collect()
+}
+
+/// The sound of each ball type that has one when collected
+pub fn collect_sounds(ball_info: &[BallInfo]) -> HashMap<BallType, String> {
+    ball_info.iter()
+        .filter_map(|info| info.sound.clone().map(|sound| (info.ball_type, sound)))
+        .collect()
+}
+
+/// The sound that each ball type that makes one keeps making
+pub fn emitters(ball_info: &[BallInfo]) -> HashMap<BallType, SoundEmitter> {
+    ball_info.iter()
+        .filter_map(|info| info.emitter.clone().map(|emitter| (info.ball_type, emitter)))
+        .collect()
+}
+
+/// Power-ups that last for a while after the ball has been collected
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Effect {
+    Shield,
+    Magnet,
+    SlowTime,
+    ConeWidener,
+}
+
+impl Effect {
+    pub fn from_ball_type(ball_type: BallType) -> Option<Effect> {
+        match ball_type {
+            BallType::Shield => Some(Effect::Shield),
+            BallType::Magnet => Some(Effect::Magnet),
+            BallType::SlowTime => Some(Effect::SlowTime),
+            BallType::ConeWidener => Some(Effect::ConeWidener),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Effect::Shield => "Shield",
+            Effect::Magnet => "Magnet",
+            Effect::SlowTime => "Slow",
+            Effect::ConeWidener => "Wide",
+        }
+    }
+}
+
+/// Resource with the effects that are active and how many seconds they have left
+pub struct ActiveEffects(pub HashMap<Effect, f32>);
+
+impl ActiveEffects {
+    pub fn is_active(&self, effect: Effect) -> bool {
+        self.0.contains_key(&effect)
+    }
+
+    /// Removes an effect, returning whether it was active
+    pub fn consume(&mut self, effect: Effect) -> bool {
+        self.0.remove(&effect).is_some()
+    }
+
+    /// Text for the HUD like `Shield 3s Magnet 5s`
+    pub fn describe(&self) -> String {
+        let mut effects = self.0.iter().collect::<Vec<_>>();
+        effects.sort_by_key(|&(effect, _)| effect.name());
+        effects.iter()
+            .map(|&(effect, remaining)| format!("{} {}s", effect.name(), remaining.ceil() as i32))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Starts, runs and ends the effects of collected power-up balls
+pub struct PowerUpSystem {
+    pub frametime: f32,
+    pub durations: HashMap<BallType, f32>,
+    // Sprite of the small balls that splitters break into
+    pub shard_sprite: Sprite,
+}
+
+impl<'a> specs::System<'a> for PowerUpSystem {
+    type SystemData = (
+        specs::Entities<'a>,
+        specs::WriteStorage<'a, Transform>,
+        specs::WriteStorage<'a, Velocity>,
+        specs::WriteStorage<'a, Sprite>,
+        specs::WriteStorage<'a, BoundingCircle>,
+        specs::WriteStorage<'a, BallType>,
+        specs::WriteStorage<'a, RigidBody>,
+        specs::WriteStorage<'a, Lifetime>,
+        specs::WriteStorage<'a, Health>,
+        specs::ReadStorage<'a, PlayerComponent>,
+        specs::Fetch<'a, BallEvents>,
+        specs::FetchMut<'a, ActiveEffects>,
+        specs::FetchMut<'a, ConeSize>,
+        specs::FetchMut<'a, TimeScale>,
+    );
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut transforms,
+            mut velocities,
+            mut sprites,
+            mut bounding_circles,
+            mut ball_types,
+            mut bodies,
+            mut lifetimes,
+            mut healths,
+            players,
+            ball_events,
+            mut active_effects,
+            mut cone_size,
+            mut time_scale
+        ) = data;
+
+        // Tick down the effects that are already running
+        for remaining in active_effects.0.values_mut() {
+            *remaining -= self.frametime;
+        }
+        active_effects.0.retain(|_, remaining| *remaining > 0.);
+
+        let mut rng = rand::thread_rng();
+        for event in &ball_events.0 {
+            let (ball_type, pos, player) = match *event {
+                BallEvent::Collected { ball_type, pos, player, .. } => (ball_type, pos, player),
+                _ => continue,
+            };
+
+            if let Some(effect) = Effect::from_ball_type(ball_type) {
+                let duration = self.durations.get(&ball_type).cloned().unwrap_or(0.);
+                active_effects.0.insert(effect, duration);
+            }
+
+            match ball_type {
+                BallType::Heal => if let Some(health) = healths.get_mut(player) {
+                    health.lives += 1;
+                },
+                BallType::Splitter => {
+                    let start_angle = rng.gen_range(0., consts::PI * 2.);
+                    for i in 0..SPLITTER_SHARDS {
+                        let angle = start_angle + consts::PI * 2. * i as f32 / SPLITTER_SHARDS as f32;
+                        let shard = entities.create();
+
+                        transforms.insert(shard, Transform {
+                            pos,
+                            angle: 0.0,
+                            scale: Vector2::new(0.15, 0.15),
+                        });
+                        velocities.insert(shard, Velocity(Vector2::new(angle.cos(), angle.sin()) * SHARD_SPEED));
+                        sprites.insert(shard, self.shard_sprite);
+                        bounding_circles.insert(shard, BoundingCircle { radius: 28.0 * 0.3 });
+                        ball_types.insert(shard, BallType::Good);
+                        bodies.insert(shard, RigidBody { mass: 0.5, restitution: 0.9 });
+                        lifetimes.insert(shard, Lifetime(SHARD_LIFETIME));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if active_effects.is_active(Effect::Magnet) {
+            let player_positions = (&transforms, &players).join()
+                .map(|(transform, _)| transform.pos)
+                .collect::<Vec<_>>();
+
+            for (transform, velocity, ball_type) in (&transforms, &mut velocities, &ball_types).join() {
+                if *ball_type != BallType::Good {
+                    continue;
+                }
+
+                let closest = player_positions.iter()
+                    .map(|&player_pos| player_pos - transform.pos)
+                    .filter(|diff| diff.norm_squared() > 0.)
+                    .min_by(|a, b| a.norm_squared().partial_cmp(&b.norm_squared()).unwrap());
+
+                if let Some(diff) = closest {
+                    velocity.0 += diff.normalize() * MAGNET_STRENGTH * self.frametime;
+                }
+            }
+        }
+
+        time_scale.0 = if active_effects.is_active(Effect::SlowTime) { SLOW_TIME_SCALE } else { 1. };
+        cone_size.multiplier = if active_effects.is_active(Effect::ConeWidener) {
+            CONE_WIDENER_MULTIPLIER
+        } else {
+            1.
+        };
+    }
+}
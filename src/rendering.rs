@@ -1,27 +1,49 @@
 extern crate specs;
-extern crate rand;
 
+use camera::{Camera, ScreenSpace};
 use constants::*;
-use components::Transform;
-use components::{HitBad, ScreenShake};
-use sprite::{Sprite, TextureManager};
+use components::{Transform, Velocity, BallType};
+use cone::{ConeSize, ViewCone};
+use dash::Dash;
+use music::MusicBeat;
+use sprite::{Sprite, TextureId, TextureManager};
+use viewport::{ScaleMode, Viewport};
 
 use sdl2::surface::{Surface, SurfaceContext};
-use sdl2::render::Canvas;
-use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Canvas, Texture};
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::Rect;
 use sdl2::video::Window;
-use std::f64::consts;
-use nalgebra::Vector2;
-use rand::Rng;
+use nalgebra::{Vector2, Dot};
 use specs::Join;
 
+fn on_screen(pos: Vector2<f32>) -> bool {
+    pos.x >= 0. && pos.x < RESOLUTION.0 as f32 && pos.y >= 0. && pos.y < RESOLUTION.1 as f32
+}
+
+fn clamp(value: f32, min: f32, max: f32) -> f32 {
+    value.max(min).min(max)
+}
+
+// Size of the stamina bar under the player, in game pixels
+const STAMINA_BAR_SIZE: (u32, u32) = (24, 3);
+const STAMINA_BAR_OFFSET: i32 = 14;
+// Off-screen balls coming towards the player are shown this far in from the edge
+const INDICATOR_MARGIN: f32 = 6.;
+const INDICATOR_SCALE: f32 = 0.06;
+// How much bigger the indicators get on the beat of the music
+const INDICATOR_PULSE: f32 = 0.3;
+// Height of the lines of text screens in game pixels, the first line is a title
+const TITLE_HEIGHT: f32 = 30.;
+const LINE_HEIGHT: f32 = 14.;
+const SCREEN_MARGIN: f32 = 10.;
+
 pub struct RenderingSystem<'l, 's: 'l> {
     pub canvas: Canvas<Window>,
     pub game_canvas: Canvas<Surface<'s>>,
     pub player: specs::Entity,
-    pub shake_amount: f32,
     pub texture_manager: TextureManager<'l, SurfaceContext<'s>>,
+    pub scale_mode: ScaleMode,
 }
 
 impl<'l, 's> RenderingSystem<'l, 's> {
@@ -29,33 +51,156 @@ impl<'l, 's> RenderingSystem<'l, 's> {
         canvas: Canvas<Window>,
         game_canvas: Canvas<Surface<'s>>,
         player: specs::Entity,
-        shake_amount: f32,
         texture_manager: TextureManager<'l, SurfaceContext<'s>>,
     ) -> RenderingSystem<'l, 's> {
         RenderingSystem {
-            canvas, game_canvas, player, shake_amount, texture_manager
+            canvas, game_canvas, player, texture_manager,
+            scale_mode: ScaleMode::Integer,
+        }
+    }
+
+    /// The part of the window that the game is drawn in
+    pub fn viewport(&self) -> Viewport {
+        let output_size = self.canvas.output_size().unwrap();
+        Viewport::fit(output_size, self.canvas.window().size(), self.scale_mode)
+    }
+
+    /// Draws a frame the size of the game into the viewport, with black bars
+    /// around it
+    fn copy_to_window(&mut self, texture: &Texture, viewport: &Viewport) {
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        self.canvas.copy(texture, None, Some(viewport.rect())).unwrap();
+    }
+
+    fn draw_stamina_bar(&mut self, player_screen_pos: Vector2<f32>, dash: &Dash, viewport: &Viewport) {
+        let x = player_screen_pos.x as i32 - STAMINA_BAR_SIZE.0 as i32 / 2;
+        let y = player_screen_pos.y as i32 + STAMINA_BAR_OFFSET;
+        let filled = (STAMINA_BAR_SIZE.0 as f32 * dash.stamina_fraction()) as u32;
+
+        // The bar is drawn straight onto the window, which may be any size
+        let scale = viewport.scale();
+        let scaled = |x: i32, y: i32, w: u32, h: u32| {
+            let pos = viewport.game_to_output(Vector2::new(x as f32, y as f32));
+            Rect::new(
+                pos.x as i32,
+                pos.y as i32,
+                (w as f32 * scale.x).ceil() as u32,
+                (h as f32 * scale.y).ceil() as u32,
+            )
+        };
+
+        self.canvas.set_draw_color(Color::RGB(40, 40, 40));
+        self.canvas.fill_rect(scaled(x, y, STAMINA_BAR_SIZE.0, STAMINA_BAR_SIZE.1)).unwrap();
+
+        if filled > 0 {
+            let color = if dash.can_dash() { Color::RGB(120, 220, 255) } else { Color::RGB(120, 120, 120) };
+            self.canvas.set_draw_color(color);
+            self.canvas.fill_rect(scaled(x, y, filled, STAMINA_BAR_SIZE.1)).unwrap();
         }
     }
+
+    /// Draws lines of text centered on an empty screen instead of the game, for
+    /// the screens before and after a game
+    pub fn draw_text_screen(&mut self, lines: &[TextureId]) {
+        let screen_camera = Camera::screen();
+        let heights = (0..lines.len())
+            .map(|i| if i == 0 { TITLE_HEIGHT } else { LINE_HEIGHT })
+            .collect::<Vec<_>>();
+        let mut y = (RESOLUTION.1 as f32 - heights.iter().sum::<f32>()) / 2.;
+
+        let background = self.game_canvas.draw_color();
+        self.game_canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.game_canvas.clear();
+
+        for (&texture_id, &height) in lines.iter().zip(heights.iter()) {
+            let size = self.texture_manager.get(texture_id).unwrap().query();
+            // Lines that are too wide are shrunk to fit the screen
+            let scale = (height / size.height as f32)
+                .min((RESOLUTION.0 as f32 - SCREEN_MARGIN * 2.) / size.width as f32);
+
+            let transform = Transform {
+                pos: Vector2::new(RESOLUTION.0 as f32 / 2., y + height / 2.),
+                angle: 0.,
+                scale: Vector2::new(scale, scale),
+            };
+            Sprite::new(texture_id).draw(&transform, &screen_camera, &mut self.game_canvas, &self.texture_manager);
+            y += height;
+        }
+        self.game_canvas.set_draw_color(background);
+
+        let texture_creator = self.canvas.texture_creator();
+        let screen_texture = texture_creator.create_texture_from_surface(self.game_canvas.surface()).unwrap();
+        let viewport = self.viewport();
+        self.copy_to_window(&screen_texture, &viewport);
+        self.canvas.present();
+    }
 }
 
 impl<'a, 'l, 's> specs::System<'a> for RenderingSystem<'l, 's> {
     type SystemData = (
         specs::ReadStorage<'a, Transform>,
         specs::ReadStorage<'a, Sprite>,
-        specs::Fetch<'a, HitBad>,
-        specs::Fetch<'a, ScreenShake>,
+        specs::Fetch<'a, ConeSize>,
+        specs::ReadStorage<'a, Dash>,
+        specs::ReadStorage<'a, Velocity>,
+        specs::ReadStorage<'a, BallType>,
+        specs::ReadStorage<'a, ScreenSpace>,
+        specs::Fetch<'a, Camera>,
+        specs::Fetch<'a, MusicBeat>,
+        specs::FetchMut<'a, Viewport>,
     );
     fn run(&mut self, data: Self::SystemData) {
-        let (transforms, sprites, hit_bad, screenshake) = data;
+        let (
+            transforms,
+            sprites,
+            cone_size,
+            dashes,
+            velocities,
+            ball_types,
+            screen_spaces,
+            camera,
+            music_beat,
+            mut viewport
+        ) = data;
+        let screen_camera = Camera::screen();
+
         //Getting some parameters about the player
         let player_transform = transforms.get(self.player).unwrap();
-        let plr_angle = player_transform.angle;
-        let player_pos = player_transform.pos;
+        let player_screen_pos = camera.world_to_screen(player_transform.pos);
+        // The colour pass works on screen pixels
+        let player_cone = camera.cone_to_screen(&ViewCone::of_player(player_transform, &cone_size));
 
         self.game_canvas.clear();
 
-        for (transform, sprite) in (&transforms, &sprites).join() {
-            sprite.draw(&transform, &mut self.game_canvas, &self.texture_manager);
+        for (transform, sprite, _) in (&transforms, &sprites, !&screen_spaces).join() {
+            if !sprite.visible {
+                continue;
+            }
+            sprite.draw(&transform, &camera, &mut self.game_canvas, &self.texture_manager);
+        }
+
+        // Small copies of off-screen balls at the edge. They are drawn before the
+        // colour pass so they are only in colour when the player looks that way
+        for (transform, sprite, velocity, _) in (&transforms, &sprites, &velocities, &ball_types).join() {
+            let screen_pos = camera.world_to_screen(transform.pos);
+            if on_screen(screen_pos) || (player_transform.pos - transform.pos).dot(&velocity.0) <= 0. {
+                continue;
+            }
+
+            let indicator = Transform {
+                pos: Vector2::new(
+                    clamp(screen_pos.x, INDICATOR_MARGIN, RESOLUTION.0 as f32 - INDICATOR_MARGIN),
+                    clamp(screen_pos.y, INDICATOR_MARGIN, RESOLUTION.1 as f32 - INDICATOR_MARGIN),
+                ),
+                angle: 0.,
+                scale: Vector2::new(1., 1.) * INDICATOR_SCALE * (1. + music_beat.pulse() * INDICATOR_PULSE),
+            };
+            sprite.draw(&indicator, &screen_camera, &mut self.game_canvas, &self.texture_manager);
+        }
+
+        for (transform, sprite, _) in (&transforms, &sprites, &screen_spaces).join() {
+            sprite.draw(&transform, &screen_camera, &mut self.game_canvas, &self.texture_manager);
         }
 
         let game_surface = self.game_canvas.surface();
@@ -72,7 +217,7 @@ impl<'a, 'l, 's> specs::System<'a> for RenderingSystem<'l, 's> {
 
             for y in 0..RESOLUTION.1 {
                 for x in 0..RESOLUTION.0 {
-                    let is_in_cone = is_in_cone(player_pos, x, y, plr_angle);
+                    let is_in_cone = player_cone.contains(Vector2::new(x as f32, y as f32));
 
                     //Doing the grayscale stuff
                     let surface_offset = (y) * game_surface.pitch() as u32 + (x) * 4;
@@ -97,64 +242,16 @@ impl<'a, 'l, 's> specs::System<'a> for RenderingSystem<'l, 's> {
             }
         }).unwrap();
         
-        //Screenshake
-        let mut offset = Vector2::new(0, 0);
-
-        let mut rng = rand::thread_rng();
-
-        if hit_bad.0 == true {
-            self.shake_amount = 10.;
-        }
-
-        if self.shake_amount > 0. {
-            offset = Vector2::new(
-                rng.gen_range(-self.shake_amount, self.shake_amount) as i32,
-                rng.gen_range(-self.shake_amount, self.shake_amount) as i32
-            );
-
-            self.shake_amount = self.shake_amount - 0.2;
-        } else if self.shake_amount < 5. {
-            self.shake_amount = 0.
-        }
+        //Render the new texture on the screen. The viewport is kept for turning
+        //mouse positions into game positions
+        *viewport = self.viewport();
+        self.copy_to_window(&game_texture, &viewport);
 
-        //Add outside screenshake stimulation
-        match screenshake.0 {
-            Some(amount) => self.shake_amount = amount,
-            None => {}
+        // The stamina bar is drawn on top so that it stays in colour
+        if let Some(dash) = dashes.get(self.player) {
+            self.draw_stamina_bar(player_screen_pos, dash, &viewport);
         }
 
-        let size = self.canvas.output_size().unwrap();
-        let screen_rect = Rect::new(offset.x, offset.y, size.0 as u32, size.1 as u32);
-
-        //Render the new texture on the screen
-        self.canvas.copy(&game_texture, Some(screen_rect), None).unwrap();
-
         self.canvas.present();
     }
 }
-
-fn is_in_cone(center: Vector2<f32>, x: u32, y: u32, angle: f64) -> bool
-{
-    let cone_size = 0.07;
-
-    let angle_threshold = cone_size * consts::PI * 2.;
-
-    //Center the pixels
-    let x = x as i32 - center.x as i32;
-    let y = y as i32 - center.y as i32;
-
-    let pixel_angle = (y as f64).atan2(x as f64);
-
-    let mut angle_diff = (angle - pixel_angle).abs();
-
-    if angle_diff > consts::PI * (1. - cone_size) * 2.
-    {
-        angle_diff = angle_diff - consts::PI * 2.;
-    }
-
-    if angle_diff < angle_threshold
-    {
-        return true;
-    }
-    false
-}
@@ -0,0 +1,288 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A value in the subset of RON that the asset manifest uses: strings, numbers,
+/// booleans, lists, maps and structs with named fields. Struct names, trailing
+/// commas and `//` comments are allowed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    // Fields in file order
+    Struct(Vec<(String, Value)>),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Result<&str, String> {
+        match *self {
+            Value::String(ref value) => Ok(value),
+            _ => Err(format!("expected a string, found {}", self.kind())),
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&[Value], String> {
+        match *self {
+            Value::List(ref values) => Ok(values),
+            _ => Err(format!("expected a list, found {}", self.kind())),
+        }
+    }
+
+    pub fn as_map(&self) -> Result<&[(Value, Value)], String> {
+        match *self {
+            Value::Map(ref entries) => Ok(entries),
+            _ => Err(format!("expected a map, found {}", self.kind())),
+        }
+    }
+
+    /// A field of a struct, None if the struct doesn't have it
+    pub fn field(&self, name: &str) -> Result<Option<&Value>, String> {
+        match *self {
+            Value::Struct(ref fields) => Ok(fields.iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value)),
+            _ => Err(format!("expected a struct, found {}", self.kind())),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match *self {
+            Value::Bool(_) => "a boolean",
+            Value::Number(_) => "a number",
+            Value::String(_) => "a string",
+            Value::List(_) => "a list",
+            Value::Map(_) => "a map",
+            Value::Struct(_) => "a struct",
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &str) -> Result<T, String> {
+        Err(format!("line {}: {}", self.line, message))
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.chars.peek().cloned() {
+                Some(c) if c.is_whitespace() => {
+                    self.next();
+                }
+                Some('/') if self.chars.clone().nth(1) == Some('/') => {
+                    // Comments run until the end of the line
+                    while !matches!(self.chars.peek(), None | Some('\n')) {
+                        self.next();
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.peek().cloned()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.next();
+                Ok(())
+            }
+            Some(c) => self.error(&format!("expected '{}', found '{}'", expected, c)),
+            None => self.error(&format!("expected '{}', found the end of the file", expected)),
+        }
+    }
+
+    /// Parses comma separated items until `end`, allowing a trailing comma
+    fn items<T, F>(&mut self, end: char, mut item: F) -> Result<Vec<T>, String>
+        where F: FnMut(&mut Parser<'a>) -> Result<T, String>
+    {
+        let mut items = vec!();
+        loop {
+            if self.peek() == Some(end) {
+                self.next();
+                return Ok(items);
+            }
+            items.push(item(self)?);
+            if self.peek() == Some(',') {
+                self.next();
+            } else {
+                self.expect(end)?;
+                return Ok(items);
+            }
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self.chars.peek().cloned() {
+            if !(c.is_alphanumeric() || c == '_' || c == '-' || c == '+' || c == '.') {
+                break;
+            }
+            word.push(c);
+            self.next();
+        }
+        word
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ '"') | Some(c @ '\\') => value.push(c),
+                    Some(c) => return self.error(&format!("unknown escape \\{}", c)),
+                    None => return self.error("unterminated string"),
+                },
+                Some(c) => value.push(c),
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('"') => self.string().map(Value::String),
+            Some('[') => {
+                self.next();
+                self.items(']', |parser| parser.value()).map(Value::List)
+            }
+            Some('{') => {
+                self.next();
+                self.items('}', |parser| {
+                    let key = parser.value()?;
+                    parser.expect(':')?;
+                    Ok((key, parser.value()?))
+                }).map(Value::Map)
+            }
+            Some('(') => self.fields(),
+            Some(_) => {
+                let word = self.word();
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    // The name of a struct is only there for people reading the file
+                    _ if word.starts_with(|c: char| c.is_alphabetic()) => self.fields(),
+                    _ => match word.parse() {
+                        Ok(number) => Ok(Value::Number(number)),
+                        Err(_) if word.is_empty() => self.error("expected a value"),
+                        Err(_) => self.error(&format!("invalid value {}", word)),
+                    },
+                }
+            }
+            None => self.error("expected a value, found the end of the file"),
+        }
+    }
+
+    fn fields(&mut self) -> Result<Value, String> {
+        self.expect('(')?;
+        self.items(')', |parser| {
+            parser.skip_whitespace();
+            let name = parser.word();
+            if name.is_empty() {
+                return parser.error("expected a field name");
+            }
+            parser.expect(':')?;
+            Ok((name, parser.value()?))
+        }).map(Value::Struct)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = Parser { chars: text.chars().peekable(), line: 1 };
+    let value = parser.value()?;
+    match parser.peek() {
+        Some(c) => parser.error(&format!("unexpected '{}' after the value", c)),
+        None => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(parse("true"), Ok(Value::Bool(true)));
+        assert_eq!(parse("-1.5"), Ok(Value::Number(-1.5)));
+        assert_eq!(parse("[1, \"a\"]"), Ok(Value::List(vec!(Value::Number(1.), string("a")))));
+        assert_eq!(
+            parse("{\"a\": false}"),
+            Ok(Value::Map(vec!((string("a"), Value::Bool(false)))))
+        );
+        assert_eq!(parse("(a: 1)"), Ok(Value::Struct(vec!(("a".to_string(), Value::Number(1.))))));
+    }
+
+    #[test]
+    fn comments() {
+        let text = "// The manifest\n(\n    // A url\n    a: \"http://example.com\", // after a value\n)\n// At the end";
+        assert_eq!(parse(text), Ok(Value::Struct(vec!(("a".to_string(), string("http://example.com"))))));
+        // A single slash is not a comment
+        assert!(parse("(a: 1 / 2)").is_err());
+    }
+
+    #[test]
+    fn trailing_commas() {
+        assert_eq!(parse("[1, 2,]"), parse("[1, 2]"));
+        assert_eq!(parse("{\"a\": 1,}"), parse("{\"a\": 1}"));
+        assert_eq!(parse("(a: 1,)"), parse("(a: 1)"));
+        assert_eq!(parse("[]"), Ok(Value::List(vec!())));
+        assert!(parse("[1,,]").is_err());
+    }
+
+    #[test]
+    fn struct_names() {
+        assert_eq!(parse("Manifest(a: 1)"), parse("(a: 1)"));
+        assert_eq!(parse("Outer(inner: Inner(b: true))"), parse("(inner: (b: true))"));
+        let value = parse("Manifest(a: 1)").unwrap();
+        assert_eq!(value.field("a"), Ok(Some(&Value::Number(1.))));
+        assert_eq!(value.field("b"), Ok(None));
+    }
+
+    #[test]
+    fn string_escapes() {
+        assert_eq!(parse(r#""a\"b\\c\nd\te""#), Ok(string("a\"b\\c\nd\te")));
+        assert_eq!(parse(r#""\x""#), Err("line 1: unknown escape \\x".to_string()));
+    }
+
+    #[test]
+    fn errors_have_line_numbers() {
+        assert_eq!(parse("(\n    a: 1,\n    b: 1x,\n)"), Err("line 3: invalid value 1x".to_string()));
+        assert_eq!(parse("(\n    a: ?,\n)"), Err("line 2: expected a value".to_string()));
+        assert_eq!(parse("(\n    a: \"open\n\n"), Err("line 4: unterminated string".to_string()));
+        assert_eq!(parse("[1]\n[2]"), Err("line 2: unexpected '[' after the value".to_string()));
+        assert_eq!(parse("(a: 1"), Err("line 1: expected ')', found the end of the file".to_string()));
+    }
+
+    #[test]
+    fn wrong_types() {
+        let value = parse("[1]").unwrap();
+        assert_eq!(value.as_str(), Err("expected a string, found a list".to_string()));
+        assert_eq!(value.field("a"), Err("expected a struct, found a list".to_string()));
+    }
+}
@@ -0,0 +1,109 @@
+extern crate specs;
+
+use nalgebra::Vector2;
+
+use components::BallType;
+use spawner::{BallEvent, BallEvents};
+
+const MULTIPLIER_STEP: f32 = 0.25;
+// Good balls caught without seeing them only grow the multiplier this much
+const BLIND_MULTIPLIER_STEP: f32 = 0.1;
+const MAX_MULTIPLIER: f32 = 5.;
+// How much the multiplier falls back towards 1 every second
+const MULTIPLIER_DECAY: f32 = 0.3;
+// Every this many good balls in a row gives an extra point per catch
+const COMBO_STEP: u32 = 5;
+// Catches made while the ball is visible in the cone are worth this much more
+const CONE_BONUS: i32 = 2;
+// Running into a bad ball that wasn't in the cone costs points...
+const BLIND_BAD_PENALTY: i32 = 5;
+// ...but grabbing a power-up without knowing what it is pays off
+const BLIND_POWERUP_BONUS: i32 = 3;
+
+/// Resource with the score of the current game. Only the `ScoreSystem` writes to it
+pub struct Score {
+    pub points: i32,
+    // Good balls caught in a row without touching a bad one
+    pub combo: u32,
+    pub max_combo: u32,
+    pub multiplier: f32,
+}
+
+impl Score {
+    pub fn new() -> Score {
+        Score {
+            points: 0,
+            combo: 0,
+            max_combo: 0,
+            multiplier: 1.,
+        }
+    }
+}
+
+/// Points that were scored this frame and where, for the floating "+N" text
+pub struct ScorePopups(pub Vec<(Vector2<f32>, i32)>);
+
+pub struct ScoreSystem {
+    pub frametime: f32,
+}
+
+impl ScoreSystem {
+    fn catch_points(score: &Score, in_cone: bool) -> i32 {
+        let base = 1 + (score.combo / COMBO_STEP) as i32;
+        let cone_bonus = if in_cone { CONE_BONUS } else { 1 };
+        ((base * cone_bonus) as f32 * score.multiplier).round() as i32
+    }
+}
+
+impl<'a> specs::System<'a> for ScoreSystem {
+    type SystemData = (
+        specs::Fetch<'a, BallEvents>,
+        specs::FetchMut<'a, Score>,
+        specs::FetchMut<'a, ScorePopups>,
+    );
+    fn run(&mut self, (ball_events, mut score, mut popups): Self::SystemData) {
+        score.multiplier = (score.multiplier - MULTIPLIER_DECAY * self.frametime).max(1.);
+
+        for event in &ball_events.0 {
+            let (ball_type, pos, in_cone) = match *event {
+                // Bad balls that did no damage don't cost anything
+                BallEvent::Collected { absorbed: true, .. } => continue,
+                BallEvent::Collected { ball_type, pos, in_cone, .. } => (ball_type, pos, in_cone),
+                _ => continue,
+            };
+
+            match ball_type {
+                BallType::Good => {
+                    score.combo += 1;
+                    score.max_combo = score.max_combo.max(score.combo);
+
+                    let points = ScoreSystem::catch_points(&score, in_cone);
+                    score.points += points;
+
+                    let step = if in_cone { MULTIPLIER_STEP } else { BLIND_MULTIPLIER_STEP };
+                    score.multiplier = (score.multiplier + step).min(MAX_MULTIPLIER);
+
+                    popups.0.push((pos, points));
+                }
+                BallType::Bad => {
+                    score.combo = 0;
+                    score.multiplier = 1.;
+
+                    let penalty = BLIND_BAD_PENALTY.min(score.points);
+                    if !in_cone && penalty > 0 {
+                        score.points -= penalty;
+                        popups.0.push((pos, -penalty));
+                    }
+                }
+                BallType::Neutral => {}
+                _ => {
+                    if !in_cone {
+                        let points = (BLIND_POWERUP_BONUS as f32 * score.multiplier).round() as i32;
+                        score.points += points;
+                        popups.0.push((pos, points));
+                    }
+                }
+            }
+        }
+    }
+}
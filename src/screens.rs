@@ -0,0 +1,122 @@
+use sdl2::ttf::Font;
+
+use highscore::{HighScore, HighScoreTable};
+use leaderboard::TopScores;
+use sprite::{TextureId, TextureManager};
+
+/// Lines of text shown instead of the game, like the title screen. Lines are
+/// only rendered again when their text changes
+pub struct TextScreen {
+    lines: Vec<(String, TextureId)>,
+}
+
+impl TextScreen {
+    pub fn new() -> TextScreen {
+        TextScreen { lines: vec!() }
+    }
+
+    pub fn set_lines<T>(&mut self, lines: &[String], font: &Font, texture_manager: &mut TextureManager<T>) -> Result<(), String> {
+        for (i, line) in lines.iter().enumerate() {
+            // Fonts can't render empty strings
+            let text = if line.is_empty() { " " } else { line.as_str() };
+
+            if i < self.lines.len() {
+                if self.lines[i].0 != text {
+                    texture_manager.make_text_texture(text, font, Some(self.lines[i].1))?;
+                    self.lines[i].0 = text.to_string();
+                }
+            } else {
+                let texture = texture_manager.make_text_texture(text, font, None)?;
+                self.lines.push((text.to_string(), texture));
+            }
+        }
+        // Lines that are no longer shown are unloaded
+        for (_, texture) in self.lines.drain(lines.len().min(self.lines.len())..) {
+            texture_manager.unload(texture);
+        }
+        Ok(())
+    }
+
+    /// Unloads all the lines, for before the scope that they were made in ends
+    pub fn clear<T>(&mut self, texture_manager: &mut TextureManager<T>) {
+        for (_, texture) in self.lines.drain(..) {
+            texture_manager.unload(texture);
+        }
+    }
+
+    pub fn textures(&self) -> Vec<TextureId> {
+        self.lines.iter().map(|&(_, texture)| texture).collect()
+    }
+}
+
+fn score_line(rank: usize, score: &HighScore) -> String {
+    format!(
+        "{:>2}. {:<12} {:>6}  combo {:<3} {}  {}",
+        rank + 1,
+        score.name,
+        score.points,
+        score.max_combo,
+        score.duration_string(),
+        score.date_string()
+    )
+}
+
+/// The high score table, with the score at `highlight` marked
+fn table_lines(table: &HighScoreTable, highlight: Option<usize>) -> Vec<String> {
+    if table.scores.is_empty() {
+        return vec!("No high scores yet".to_string());
+    }
+    table.scores.iter().enumerate()
+        .map(|(rank, score)| {
+            let marker = if Some(rank) == highlight { ">" } else { " " };
+            format!("{}{}", marker, score_line(rank, score))
+        })
+        .collect()
+}
+
+fn online_lines(top: &TopScores) -> Vec<String> {
+    match *top {
+        TopScores::NotFetched | TopScores::Loading => vec!("Loading...".to_string()),
+        TopScores::Failed(_) => vec!("Could not reach the leaderboard".to_string()),
+        TopScores::Fetched(ref scores) if scores.is_empty() => vec!("No online scores yet".to_string()),
+        TopScores::Fetched(ref scores) => scores.iter().enumerate()
+            .map(|(rank, score)| format!(" {}", score_line(rank, score)))
+            .collect(),
+    }
+}
+
+/// The title screen, with the online leaderboard if there is one
+pub fn title_lines(table: &HighScoreTable, online: Option<&TopScores>) -> Vec<String> {
+    let mut lines = vec!("Nihil Chroma".to_string(), String::new(), "High scores".to_string());
+    lines.extend(table_lines(table, None));
+    if let Some(top) = online {
+        lines.push(String::new());
+        lines.push("Online".to_string());
+        lines.extend(online_lines(top));
+    }
+    lines.push(String::new());
+    lines.push("Press Enter to start, O for options".to_string());
+    lines
+}
+
+pub fn name_entry_lines(points: i32, rank: usize, name: &str) -> Vec<String> {
+    vec!(
+        "Game over".to_string(),
+        String::new(),
+        format!("Score: {}", points),
+        format!("New high score! Place {}", rank + 1),
+        String::new(),
+        "Enter your name:".to_string(),
+        format!("{}_", name),
+        String::new(),
+        "Press Enter to save".to_string(),
+    )
+}
+
+pub fn game_over_lines(table: &HighScoreTable, points: i32, rank: Option<usize>) -> Vec<String> {
+    let mut lines = vec!("Game over".to_string(), String::new(), format!("Score: {}", points), String::new());
+    lines.extend(table_lines(table, rank));
+    lines.push(String::new());
+    lines.push("Press Enter to quit".to_string());
+    lines
+}
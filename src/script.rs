@@ -0,0 +1,364 @@
+extern crate specs;
+
+use std::collections::HashMap;
+
+use specs::{Entity, World};
+
+use assets::Assets;
+use components::{Transform, BoundingCircle, BallType, OrbitComponent, ScreenShake};
+use config::Section;
+use music::MusicState;
+use sprite::Sprite;
+
+/// Something scripts can spawn by name
+#[derive(Copy, Clone)]
+pub struct Prefab {
+    pub sprite: Sprite,
+    pub transform: Transform,
+    // Prefabs with a ball type can be hit by the player like any other ball
+    pub collision: Option<(BoundingCircle, BallType)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OrbitAngle {
+    Fixed(f32),
+    // Start at the current angle of the first entity with this tag
+    Tag(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrbitSpec {
+    pub radius: f32,
+    pub target_radius: f32,
+    pub angle: OrbitAngle,
+    pub angular_velocity: f32,
+}
+
+impl OrbitSpec {
+    /// Parses `radius target_radius angle angular_velocity` where the angle can be
+    /// `@tag` to copy the angle of a tagged entity
+    fn parse(text: &str) -> Result<OrbitSpec, String> {
+        let words = text.split_whitespace().collect::<Vec<_>>();
+        if words.len() != 4 {
+            return Err(format!("expected 'radius target_radius angle angular_velocity', got {}", text));
+        }
+
+        let number = |word: &str| word.parse::<f32>()
+            .map_err(|_| format!("invalid number in orbit: {}", word));
+
+        let angle = if words[2].starts_with('@') {
+            OrbitAngle::Tag(words[2][1..].to_string())
+        } else {
+            OrbitAngle::Fixed(number(words[2])?)
+        };
+
+        Ok(OrbitSpec {
+            radius: number(words[0])?,
+            target_radius: number(words[1])?,
+            angle,
+            angular_velocity: number(words[3])?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    ScoreAtLeast(i32),
+    LifeAtMost(i32),
+    // At least one entity with the tag is alive
+    Alive(String),
+    // Every entity with the tag is gone
+    Gone(String),
+}
+
+impl Condition {
+    /// Parses conditions like `score >= 50`, `life <= 1` or `gone sausage`
+    fn parse(text: &str) -> Result<Condition, String> {
+        let words = text.split_whitespace().collect::<Vec<_>>();
+        let number = |word: &str| word.parse::<i32>()
+            .map_err(|_| format!("invalid number in condition: {}", text));
+
+        match words.as_slice() {
+            ["score", ">=", amount] => Ok(Condition::ScoreAtLeast(number(amount)?)),
+            ["life", "<=", amount] => Ok(Condition::LifeAtMost(number(amount)?)),
+            ["alive", tag] => Ok(Condition::Alive(tag.to_string())),
+            ["gone", tag] => Ok(Condition::Gone(tag.to_string())),
+            _ => Err(format!("unknown condition: {}", text)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    Spawn { prefab: String, count: u32, tag: Option<String>, orbit: Option<OrbitSpec> },
+    SetOrbit { tag: String, orbit: OrbitSpec },
+    Shake(f32),
+    PlaySound(String),
+    ChangeMusic(Option<MusicState>),
+}
+
+impl Action {
+    fn from_section(section: &Section) -> Result<Action, String> {
+        let orbit = match section.get_str("orbit") {
+            Some(orbit) => Some(OrbitSpec::parse(orbit)?),
+            None => None,
+        };
+
+        if let Some(prefab) = section.get_str("spawn") {
+            Ok(Action::Spawn {
+                prefab: prefab.to_string(),
+                count: section.get_or("count", 1)?,
+                tag: section.get_str("tag").map(String::from),
+                orbit,
+            })
+        } else if let Some(tag) = section.get_str("set_orbit") {
+            Ok(Action::SetOrbit {
+                tag: tag.to_string(),
+                orbit: orbit.ok_or("set_orbit needs an orbit")?,
+            })
+        } else if let Some(amount) = section.get("shake")? {
+            Ok(Action::Shake(amount))
+        } else if let Some(sound) = section.get_str("play_sound") {
+            Ok(Action::PlaySound(sound.to_string()))
+        } else if let Some(track) = section.get_str("change_music") {
+            match (track, MusicState::from_name(track)) {
+                ("adaptive", _) => Ok(Action::ChangeMusic(None)),
+                (_, Some(state)) => Ok(Action::ChangeMusic(Some(state))),
+                _ => Err(format!("unknown music track {}", track)),
+            }
+        } else {
+            Err("step without an action".into())
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Step {
+    // Seconds since the start of the game before the step can run
+    pub at: f32,
+    pub condition: Option<Condition>,
+    // Run the action every frame once the step has triggered instead of only once
+    pub repeat: bool,
+    pub action: Action,
+}
+
+#[derive(Clone, Debug)]
+pub struct Script {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+pub fn load_script(path: &str, assets: &Assets) -> Result<Script, String> {
+    let sections = assets.load_config(path)?;
+
+    let name = sections.iter()
+        .find(|section| section.name == "script")
+        .and_then(|section| section.get_str("name"))
+        .unwrap_or(path)
+        .to_string();
+
+    let steps = sections.iter()
+        .filter(|section| section.name == "step")
+        .map(|section| {
+            Ok(Step {
+                at: section.get_or("at", 0.)?,
+                condition: match section.get_str("when") {
+                    Some(condition) => Some(Condition::parse(condition)?),
+                    None => None,
+                },
+                repeat: section.get_or("repeat", false)?,
+                action: Action::from_section(section)?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(|e| format!("{}: {}", path, e))?;
+
+    Ok(Script { name, steps })
+}
+
+/// Loads the scripts in the order that they are listed in
+pub fn load_scripts(paths: &[String], assets: &Assets) -> Result<Vec<Script>, String> {
+    paths.iter()
+        .map(|path| load_script(path, assets))
+        .collect()
+}
+
+/// Actions that the script runner can not carry out on the world by itself
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptCommand {
+    PlaySound(String),
+    // A track to play regardless of the game state, or None to go back to
+    // adaptive music
+    ChangeMusic(Option<MusicState>),
+}
+
+/// The parts of the game state that conditions can look at
+pub struct ScriptContext {
+    pub elapsed: f32,
+    pub points: i32,
+    pub life: i32,
+}
+
+pub struct ScriptRunner {
+    scripts: Vec<Script>,
+    // Whether each step of each script has triggered
+    triggered: Vec<Vec<bool>>,
+    prefabs: HashMap<String, Prefab>,
+    tags: HashMap<String, Vec<Entity>>,
+}
+
+impl ScriptRunner {
+    /// Fails if a script spawns a prefab that isn't in `prefabs`
+    pub fn new(scripts: Vec<Script>, prefabs: HashMap<String, Prefab>) -> Result<ScriptRunner, String> {
+        for script in &scripts {
+            for step in &script.steps {
+                if let Action::Spawn { ref prefab, .. } = step.action {
+                    if !prefabs.contains_key(prefab) {
+                        return Err(format!("script {} spawns unknown prefab {}", script.name, prefab));
+                    }
+                }
+            }
+        }
+
+        let triggered = scripts.iter()
+            .map(|script| vec!(false; script.steps.len()))
+            .collect();
+
+        Ok(ScriptRunner {
+            scripts,
+            triggered,
+            prefabs,
+            tags: HashMap::new(),
+        })
+    }
+
+    /// Runs all steps that are due and returns the commands that the caller has to
+    /// carry out
+    pub fn update(&mut self, world: &mut World, context: &ScriptContext) -> Vec<ScriptCommand> {
+        let mut due = vec!();
+        for (script_index, script) in self.scripts.iter().enumerate() {
+            for (step_index, step) in script.steps.iter().enumerate() {
+                let triggered = self.triggered[script_index][step_index];
+                if (triggered && !step.repeat) || context.elapsed < step.at {
+                    continue;
+                }
+
+                let condition_holds = match step.condition {
+                    Some(ref condition) => self.check_condition(world, condition, context),
+                    None => true,
+                };
+                if condition_holds {
+                    due.push((script_index, step_index));
+                }
+            }
+        }
+
+        let mut commands = vec!();
+        for (script_index, step_index) in due {
+            self.triggered[script_index][step_index] = true;
+
+            let action = self.scripts[script_index].steps[step_index].action.clone();
+            if let Some(command) = self.run_action(world, action) {
+                commands.push(command);
+            }
+        }
+        commands
+    }
+
+    /// Seconds until the first pending step that spawns something with the tag
+    pub fn time_until_spawn(&self, tag: &str, elapsed: f32) -> Option<f32> {
+        self.scripts.iter()
+            .zip(self.triggered.iter())
+            .flat_map(|(script, triggered)| script.steps.iter().zip(triggered.iter()))
+            .filter(|&(step, &triggered)| !triggered && match step.action {
+                Action::Spawn { tag: Some(ref step_tag), .. } => step_tag == tag,
+                _ => false,
+            })
+            .map(|(step, _)| (step.at - elapsed).max(0.))
+            .fold(None, |soonest: Option<f32>, time| Some(soonest.map_or(time, |soonest| soonest.min(time))))
+    }
+
+    /// Whether anything spawned with the tag is still around
+    pub fn is_alive(&self, world: &World, tag: &str) -> bool {
+        !self.tagged_alive(world, tag).is_empty()
+    }
+
+    fn tagged_alive(&self, world: &World, tag: &str) -> Vec<Entity> {
+        self.tags.get(tag)
+            .map(|entities| entities.iter().cloned().filter(|&entity| world.is_alive(entity)).collect())
+            .unwrap_or_default()
+    }
+
+    fn check_condition(&self, world: &World, condition: &Condition, context: &ScriptContext) -> bool {
+        match *condition {
+            Condition::ScoreAtLeast(amount) => context.points >= amount,
+            Condition::LifeAtMost(amount) => context.life <= amount,
+            Condition::Alive(ref tag) => self.is_alive(world, tag),
+            Condition::Gone(ref tag) => !self.is_alive(world, tag),
+        }
+    }
+
+    fn make_orbit(&self, world: &World, spec: &OrbitSpec) -> OrbitComponent {
+        let angle = match spec.angle {
+            OrbitAngle::Fixed(angle) => angle,
+            OrbitAngle::Tag(ref tag) => {
+                let orbits = world.read::<OrbitComponent>();
+                self.tagged_alive(world, tag).iter()
+                    .filter_map(|&entity| orbits.get(entity))
+                    .map(|orbit| orbit.angle)
+                    .next()
+                    .unwrap_or(0.)
+            }
+        };
+
+        OrbitComponent {
+            radius: spec.radius,
+            target_radius: spec.target_radius,
+            angle,
+            angular_velocity: spec.angular_velocity,
+        }
+    }
+
+    fn run_action(&mut self, world: &mut World, action: Action) -> Option<ScriptCommand> {
+        match action {
+            Action::Spawn { prefab, count, tag, orbit } => {
+                // Checked when the runner was made
+                let prefab = self.prefabs[&prefab];
+
+                for _ in 0..count {
+                    let orbit = orbit.as_ref().map(|spec| self.make_orbit(world, spec));
+
+                    let mut builder = world.create_entity()
+                        .with(prefab.transform)
+                        .with(prefab.sprite);
+                    if let Some(orbit) = orbit {
+                        builder = builder.with(orbit);
+                    }
+                    if let Some((bounds, ball_type)) = prefab.collision {
+                        builder = builder.with(bounds).with(ball_type);
+                    }
+                    let entity = builder.build();
+
+                    if let Some(ref tag) = tag {
+                        self.tags.entry(tag.clone()).or_insert_with(Vec::new).push(entity);
+                    }
+                }
+                None
+            }
+            Action::SetOrbit { tag, orbit } => {
+                let orbit = self.make_orbit(world, &orbit);
+                let mut orbits = world.write::<OrbitComponent>();
+                for entity in self.tagged_alive(world, &tag) {
+                    orbits.insert(entity, orbit);
+                }
+                None
+            }
+            Action::Shake(amount) => {
+                *world.write_resource::<ScreenShake>() = ScreenShake(Some(amount));
+                None
+            }
+            Action::PlaySound(sound) => Some(ScriptCommand::PlaySound(sound)),
+            Action::ChangeMusic(track) => Some(ScriptCommand::ChangeMusic(track)),
+        }
+    }
+}
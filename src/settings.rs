@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use sdl2::keyboard::Keycode;
+
+use audio::SoundGroup;
+use cone::ConeMode;
+use config::{self, Section};
+use paths;
+use player::Keys;
+use viewport::{ScaleMode, WindowMode};
+
+// Version of the settings file written by this version of the game
+const SETTINGS_VERSION: u32 = 2;
+const FILE_NAME: &str = "settings.txt";
+// Upgrades a settings file by one version, `MIGRATIONS[0]` turns version 1
+// into version 2 and so on. Add one whenever the format changes
+const MIGRATIONS: &[fn(&mut [Section])] = &[
+    migrate_fullscreen,
+];
+
+pub const MAX_SCALE: u32 = 4;
+
+/// Resource with everything that the player can change in the options menu
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub master_volume: f32,
+    // Scales the volume that each group has in data/sounds.txt
+    pub volumes: HashMap<SoundGroup, f32>,
+    // Size of the window as a multiple of the game resolution
+    pub scale: u32,
+    pub window_mode: WindowMode,
+    pub scale_mode: ScaleMode,
+    // Only takes effect when the game starts
+    pub vsync: bool,
+    pub shake: f32,
+    pub cone_mode: ConeMode,
+    pub bindings: HashMap<Keys, Keycode>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            master_volume: 1.,
+            volumes: SoundGroup::all().iter().map(|&group| (group, 1.)).collect(),
+            scale: 2,
+            window_mode: WindowMode::Windowed,
+            scale_mode: ScaleMode::Integer,
+            vsync: false,
+            shake: 1.,
+            cone_mode: ConeMode::Mouse,
+            bindings: default_bindings(),
+        }
+    }
+}
+
+pub fn default_bindings() -> HashMap<Keys, Keycode> {
+    let mut bindings = HashMap::new();
+    bindings.insert(Keys::Up, Keycode::W);
+    bindings.insert(Keys::Down, Keycode::S);
+    bindings.insert(Keys::Left, Keycode::A);
+    bindings.insert(Keys::Right, Keycode::D);
+    bindings.insert(Keys::Dash, Keycode::Space);
+    bindings
+}
+
+impl Settings {
+    pub fn volume(&self, group: SoundGroup) -> f32 {
+        self.volumes.get(&group).cloned().unwrap_or(1.)
+    }
+
+    /// The action that `keycode` is bound to, if any
+    pub fn action_for(&self, keycode: Keycode) -> Option<Keys> {
+        self.bindings.iter()
+            .find(|&(_, &bound)| bound == keycode)
+            .map(|(&action, _)| action)
+    }
+
+    /// Binds `keycode` to `action`. An action that had the key before swaps
+    /// keys with it, so every action keeps a key
+    pub fn bind(&mut self, action: Keys, keycode: Keycode) {
+        if let Some(old_key) = self.bindings.get(&action).cloned() {
+            if let Some(other) = self.action_for(keycode) {
+                self.bindings.insert(other, old_key);
+            }
+        }
+        self.bindings.insert(action, keycode);
+    }
+
+    /// Reads the settings from sections of the current version. Missing values
+    /// keep their defaults
+    fn from_sections(sections: &[Section]) -> Result<Settings, String> {
+        let mut settings = Settings::default();
+
+        for section in sections {
+            match section.name.as_str() {
+                "audio" => {
+                    settings.master_volume = section.get_or("master", settings.master_volume)?;
+                    for &group in &SoundGroup::all() {
+                        if let Some(volume) = section.get(group.name())? {
+                            settings.volumes.insert(group, volume);
+                        }
+                    }
+                }
+                "video" => {
+                    settings.scale = section.get_or("scale", settings.scale)?;
+                    if let Some(mode) = section.get_str("window") {
+                        settings.window_mode = WindowMode::from_name(mode)
+                            .ok_or_else(|| format!("unknown window mode {}", mode))?;
+                    }
+                    if let Some(mode) = section.get_str("scaling") {
+                        settings.scale_mode = ScaleMode::from_name(mode)
+                            .ok_or_else(|| format!("unknown scaling mode {}", mode))?;
+                    }
+                    settings.vsync = section.get_or("vsync", settings.vsync)?;
+                }
+                "gameplay" => {
+                    settings.shake = section.get_or("shake", settings.shake)?;
+                    if let Some(mode) = section.get_str("cone") {
+                        settings.cone_mode = ConeMode::from_name(mode)
+                            .ok_or_else(|| format!("unknown cone mode {}", mode))?;
+                    }
+                }
+                "keys" => for &action in &Keys::all() {
+                    if let Some(name) = section.get_str(action.name()) {
+                        let keycode = Keycode::from_name(name).ok_or_else(|| format!("unknown key {}", name))?;
+                        settings.bind(action, keycode);
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        settings.scale = settings.scale.max(1).min(MAX_SCALE);
+        Ok(settings)
+    }
+
+    fn to_sections(&self) -> Vec<Section> {
+        let mut header = Section::new("settings");
+        header.set("version", SETTINGS_VERSION);
+
+        let mut audio = Section::new("audio");
+        audio.set("master", self.master_volume);
+        for &group in &SoundGroup::all() {
+            audio.set(group.name(), self.volume(group));
+        }
+
+        let mut video = Section::new("video");
+        video.set("scale", self.scale);
+        video.set("window", self.window_mode.name());
+        video.set("scaling", self.scale_mode.name());
+        video.set("vsync", self.vsync);
+
+        let mut gameplay = Section::new("gameplay");
+        gameplay.set("shake", self.shake);
+        gameplay.set("cone", self.cone_mode.name());
+
+        let mut keys = Section::new("keys");
+        for &action in &Keys::all() {
+            if let Some(keycode) = self.bindings.get(&action) {
+                keys.set(action.name(), keycode.name());
+            }
+        }
+
+        vec!(header, audio, video, gameplay, keys)
+    }
+}
+
+/// Version 1 only had `fullscreen = true/false` which always meant borderless
+/// fullscreen. Version 2 replaced it with `window` to also allow exclusive
+/// fullscreen
+fn migrate_fullscreen(sections: &mut [Section]) {
+    for section in sections.iter_mut().filter(|section| section.name == "video") {
+        if let Some(fullscreen) = section.remove("fullscreen") {
+            let mode = if fullscreen == "true" { WindowMode::Borderless } else { WindowMode::Windowed };
+            section.set("window", mode.name());
+        }
+    }
+}
+
+/// Brings sections written by an older version of the game up to date
+fn migrate(sections: &mut [Section]) -> Result<(), String> {
+    let version = sections.iter()
+        .find(|section| section.name == "settings")
+        .map_or(Ok(None), |section| section.get::<u32>("version"))?
+        // Files written by hand may leave out the version
+        .unwrap_or(SETTINGS_VERSION);
+
+    if version == 0 || version > SETTINGS_VERSION {
+        return Err(format!("unsupported version {}", version));
+    }
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        migration(sections);
+    }
+    Ok(())
+}
+
+/// The settings along with the file that they are stored in
+pub struct SettingsFile {
+    path: Option<PathBuf>,
+}
+
+impl SettingsFile {
+    /// Loads the settings from the config directory. Settings that can't be
+    /// read are moved out of the way and replaced by the defaults
+    pub fn load() -> (SettingsFile, Settings) {
+        let path = match paths::config_dir() {
+            Ok(dir) => dir.join(FILE_NAME),
+            Err(e) => {
+                println!("Settings will not be saved: {}", e);
+                return (SettingsFile { path: None }, Settings::default());
+            }
+        };
+        if !path.exists() {
+            return (SettingsFile { path: Some(path) }, Settings::default());
+        }
+
+        let result = config::load(&path.to_string_lossy()).and_then(|mut sections| {
+            migrate(&mut sections)?;
+            Settings::from_sections(&sections)
+        });
+        let settings = match result {
+            Ok(settings) => settings,
+            Err(e) => {
+                let broken_path = path.with_extension("txt.broken");
+                println!("Could not read settings, moving them to {}: {}", broken_path.display(), e);
+                if let Err(e) = fs::rename(&path, &broken_path) {
+                    println!("Could not move {}: {}", path.display(), e);
+                }
+                Settings::default()
+            }
+        };
+        (SettingsFile { path: Some(path) }, settings)
+    }
+
+    pub fn save(&self, settings: &Settings) -> Result<(), String> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        config::save(&path.to_string_lossy(), "Nihil Chroma settings", &settings.to_sections())
+    }
+}
@@ -0,0 +1,127 @@
+extern crate specs;
+
+use std::collections::HashMap;
+
+use nalgebra::Vector2;
+
+use camera::Camera;
+use components::{HitBad, ScreenShake};
+
+// Offset and rotation at full trauma, in screen pixels and radians
+const MAX_OFFSET: f32 = 12.;
+const MAX_ROTATION: f32 = 0.06;
+// Trauma lost per second
+const TRAUMA_DECAY: f32 = 1.2;
+// How quickly the noise changes, in noise cells per second
+const NOISE_FREQUENCY: f32 = 15.;
+
+/// Things that shake the camera
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ShakeSource {
+    // The player was hit by a bad ball
+    Damage,
+    // A `shake` step of a script
+    Script,
+}
+
+/// Resource with the current amount of camera shake.
+///
+/// Sources add trauma between 0 and 1 which decays over time. The shake grows
+/// with the square of the trauma so small hits stay subtle.
+pub struct CameraShake {
+    pub trauma: f32,
+    // Accessibility setting multiplying all shake, 0 turns it off
+    pub scale: f32,
+    // Trauma added by each source, scaled by how strong the event is
+    pub intensities: HashMap<ShakeSource, f32>,
+    time: f32,
+}
+
+impl CameraShake {
+    pub fn new(scale: f32) -> CameraShake {
+        let mut intensities = HashMap::new();
+        intensities.insert(ShakeSource::Damage, 0.6);
+        intensities.insert(ShakeSource::Script, 1.);
+
+        CameraShake {
+            trauma: 0.,
+            scale,
+            intensities,
+            time: 0.,
+        }
+    }
+
+    fn intensity(&self, source: ShakeSource) -> f32 {
+        self.intensities.get(&source).cloned().unwrap_or(0.)
+    }
+
+    pub fn add_trauma(&mut self, source: ShakeSource, amount: f32) {
+        self.trauma = (self.trauma + amount * self.intensity(source)).min(1.);
+    }
+
+    /// Makes sure that the trauma is at least `amount`, for sources that keep
+    /// shaking the camera every frame
+    pub fn hold_trauma(&mut self, source: ShakeSource, amount: f32) {
+        self.trauma = self.trauma.max((amount * self.intensity(source)).min(1.));
+    }
+
+    fn strength(&self) -> f32 {
+        self.trauma * self.trauma * self.scale
+    }
+}
+
+/// Smooth pseudo random value between -1 and 1. One dimensional Perlin noise
+/// where `seed` picks an independent noise channel
+fn perlin(seed: u32, x: f32) -> f32 {
+    let cell = x.floor();
+    let t = x - cell;
+
+    let gradient = |lattice: i32| {
+        let mut hash = (lattice as u32).wrapping_mul(0x27d4_eb2d) ^ seed.wrapping_mul(0x1656_67b1);
+        hash ^= hash >> 15;
+        hash = hash.wrapping_mul(0x85eb_ca6b);
+        hash ^= hash >> 13;
+        (hash & 0xffff) as f32 / 0x7fff as f32 - 1.
+    };
+
+    let left = gradient(cell as i32) * t;
+    let right = gradient(cell as i32 + 1) * (t - 1.);
+    let fade = t * t * t * (t * (t * 6. - 15.) + 10.);
+
+    // The largest value 1D gradient noise reaches is 0.5
+    (left + (right - left) * fade) * 2.
+}
+
+/// Turns shake events into trauma, and trauma into camera offset and rotation
+pub struct ShakeSystem {
+    pub frametime: f32,
+}
+
+impl<'a> specs::System<'a> for ShakeSystem {
+    type SystemData = (
+        specs::Fetch<'a, HitBad>,
+        specs::FetchMut<'a, ScreenShake>,
+        specs::FetchMut<'a, CameraShake>,
+        specs::FetchMut<'a, Camera>,
+    );
+    fn run(&mut self, (hit_bad, mut screen_shake, mut shake, mut camera): Self::SystemData) {
+        if hit_bad.0 {
+            shake.add_trauma(ShakeSource::Damage, 1.);
+        }
+        if let Some(amount) = screen_shake.0.take() {
+            shake.hold_trauma(ShakeSource::Script, amount);
+        }
+
+        shake.time += self.frametime;
+        let noise_time = shake.time * NOISE_FREQUENCY;
+        let strength = shake.strength();
+
+        camera.shake_offset = Vector2::new(
+            perlin(0, noise_time) * MAX_OFFSET * strength,
+            perlin(1, noise_time) * MAX_OFFSET * strength,
+        );
+        camera.shake_rotation = (perlin(2, noise_time) * MAX_ROTATION * strength) as f64;
+
+        shake.trauma = (shake.trauma - TRAUMA_DECAY * self.frametime).max(0.);
+    }
+}
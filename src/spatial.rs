@@ -0,0 +1,137 @@
+extern crate specs;
+extern crate rand;
+
+use std::collections::HashMap;
+
+use nalgebra::{Vector2, Norm};
+use rand::Rng;
+
+use specs::{Join, VecStorage};
+
+use components::{BallType, Transform};
+use player::PlayerComponent;
+use spawner::{BallEvent, BallEvents};
+
+// Sounds further away from the player than this can't be heard
+const HEARING_RANGE: f32 = 250.;
+// Only this many of the closest looping sounds play at the same time
+pub const MAX_LOOPS: usize = 4;
+
+/// Where a sound is heard from, in the terms of `Mix_SetPosition`
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StereoPosition {
+    // Degrees clockwise from straight ahead
+    pub angle: i16,
+    // 0 is right next to the listener, 255 is as far away as can be heard
+    pub distance: u8,
+}
+
+impl StereoPosition {
+    /// The position of `pos` as heard by a listener looking along their angle,
+    /// or None if it is too far away to be heard
+    pub fn relative_to(listener: &Transform, pos: Vector2<f32>) -> Option<StereoPosition> {
+        let diff = pos - listener.pos;
+        let distance = diff.norm();
+        if distance > HEARING_RANGE {
+            return None;
+        }
+
+        // The y axis points down so angles already go clockwise
+        let world_angle = (diff.y as f64).atan2(diff.x as f64);
+        let relative = (world_angle - listener.angle).to_degrees();
+
+        Some(StereoPosition {
+            angle: (relative.round() as i32).rem_euclid(360) as i16,
+            distance: (distance / HEARING_RANGE * 255.) as u8,
+        })
+    }
+}
+
+#[derive(Component, Clone, Debug, PartialEq)]
+#[component(VecStorage)]
+/// A sound that a ball keeps making so that players can hear where it is
+pub struct SoundEmitter {
+    pub sound: String,
+    // Seconds between plays, or None for a sound that loops
+    pub interval: Option<f32>,
+    // Seconds until the next play
+    timer: f32,
+}
+
+impl SoundEmitter {
+    pub fn new(sound: String, interval: Option<f32>) -> SoundEmitter {
+        SoundEmitter { sound, interval, timer: 0. }
+    }
+}
+
+/// Resource with the sounds that emitters want played this frame, relative to
+/// the player. Played by the `AudioManager` in the main loop
+pub struct EmittedSounds {
+    pub once: Vec<(String, StereoPosition)>,
+    // Entity id, sound and position of the looping sounds that should be playing
+    pub loops: Vec<(u32, String, StereoPosition)>,
+}
+
+/// Gives new balls the emitter of their type and works out where the player
+/// hears every emitter from
+pub struct SpatialAudioSystem {
+    pub frametime: f32,
+    pub emitters: HashMap<BallType, SoundEmitter>,
+}
+
+impl<'a> specs::System<'a> for SpatialAudioSystem {
+    type SystemData = (
+        specs::Entities<'a>,
+        specs::ReadStorage<'a, Transform>,
+        specs::ReadStorage<'a, PlayerComponent>,
+        specs::WriteStorage<'a, SoundEmitter>,
+        specs::Fetch<'a, BallEvents>,
+        specs::FetchMut<'a, EmittedSounds>,
+    );
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, transforms, players, mut emitters, ball_events, mut emitted) = data;
+        let mut rng = rand::thread_rng();
+
+        for event in &ball_events.0 {
+            if let BallEvent::Spawned { entity, ball_type } = *event {
+                if let Some(emitter) = self.emitters.get(&ball_type) {
+                    // Start at a random point so that balls don't all play at once
+                    let interval = emitter.interval.unwrap_or(0.);
+                    let mut emitter = emitter.clone();
+                    emitter.timer = rng.gen_range(0., interval.max(0.01));
+                    emitters.insert(entity, emitter);
+                }
+            }
+        }
+
+        emitted.once.clear();
+        emitted.loops.clear();
+
+        let listener = match (&transforms, &players).join().next() {
+            Some((transform, _)) => *transform,
+            None => return,
+        };
+
+        for (entity, transform, emitter) in (&*entities, &transforms, &mut emitters).join() {
+            let position = StereoPosition::relative_to(&listener, transform.pos);
+
+            match emitter.interval {
+                Some(interval) => {
+                    emitter.timer -= self.frametime;
+                    if emitter.timer <= 0. {
+                        emitter.timer += interval;
+                        if let Some(position) = position {
+                            emitted.once.push((emitter.sound.clone(), position));
+                        }
+                    }
+                }
+                None => if let Some(position) = position {
+                    emitted.loops.push((entity.id(), emitter.sound.clone(), position));
+                },
+            }
+        }
+
+        emitted.loops.sort_by_key(|&(_, _, position)| position.distance);
+        emitted.loops.truncate(MAX_LOOPS);
+    }
+}
@@ -0,0 +1,162 @@
+extern crate specs;
+extern crate rand;
+
+use nalgebra::Vector2;
+
+use specs::{Entity, Join, World};
+
+use components::{Transform, Velocity, BallType, BoundingCircle, RigidBody};
+use director::SpawnRules;
+use game::RespawnComponent;
+use sprite::Sprite;
+
+/// Something that happened to a pooled ball this frame
+#[derive(Copy, Clone, Debug)]
+pub enum BallEvent {
+    Spawned { entity: Entity, ball_type: BallType },
+    // Pooled balls get replaced by the spawner, others are just gone
+    Collected {
+        ball_type: BallType,
+        pos: Vector2<f32>,
+        pooled: bool,
+        in_cone: bool,
+        // A bad ball that did no harm, because of a shield, invulnerability or
+        // a dash
+        absorbed: bool,
+        player: Entity,
+    },
+}
+
+/// Ball events since the last time the spawner ran. Systems push events here and
+/// everything that runs before the next `BallSpawner::do_spawn` can read them
+pub struct BallEvents(pub Vec<BallEvent>);
+
+/// Resource keeping track of how many balls may exist and which ones are
+/// waiting to be respawned
+pub struct BallPool {
+    pub max_balls: usize,
+    /// Seconds between a ball being collected and a new one taking its place
+    pub respawn_delay: f32,
+    pending_respawns: Vec<f32>,
+}
+
+impl BallPool {
+    pub fn new(max_balls: usize, respawn_delay: f32) -> BallPool {
+        BallPool {
+            max_balls,
+            respawn_delay,
+            pending_respawns: vec!(),
+        }
+    }
+
+    pub fn pending_respawns(&self) -> usize {
+        self.pending_respawns.len()
+    }
+}
+
+pub fn live_balls(world: &World) -> usize {
+    let ball_types = world.read::<BallType>();
+    let respawns = world.read::<RespawnComponent>();
+    (&ball_types, &respawns).join().count()
+}
+
+pub struct BallSpawner {
+    last_spawn: f32,
+
+    types: Vec<(BallType, Sprite)>,
+}
+
+impl BallSpawner {
+    pub fn new(types: Vec<(BallType, Sprite)>) -> BallSpawner {
+        BallSpawner {
+            last_spawn: 0.,
+
+            types: types,
+        }
+    }
+
+    pub fn do_spawn(&mut self, world: &mut World, curr_time: f32) {
+        // Schedule a replacement for every ball that was collected since last time
+        let collected = world.read_resource::<BallEvents>().0.iter()
+            .filter(|event| match **event {
+                BallEvent::Collected { pooled, .. } => pooled,
+                _ => false
+            })
+            .count();
+        world.write_resource::<BallEvents>().0.clear();
+
+        let due_respawns = {
+            let mut pool = world.write_resource::<BallPool>();
+            for _ in 0..collected {
+                let respawn_time = curr_time + pool.respawn_delay;
+                pool.pending_respawns.push(respawn_time);
+            }
+
+            let before = pool.pending_respawns.len();
+            pool.pending_respawns.retain(|&time| time > curr_time);
+            before - pool.pending_respawns.len()
+        };
+
+        for _ in 0..due_respawns {
+            self.try_spawn_ball(world);
+        }
+
+        let spawn_time = world.read_resource::<SpawnRules>().spawn_time;
+        if curr_time > self.last_spawn + spawn_time {
+            self.try_spawn_ball(world);
+
+            self.last_spawn = curr_time;
+        }
+    }
+
+    /// Spawns a ball unless the pool is full. Balls waiting to be respawned count
+    /// towards the limit
+    pub fn try_spawn_ball(&self, world: &mut World) -> Option<Entity> {
+        let (max_balls, pending) = {
+            let pool = world.read_resource::<BallPool>();
+            (pool.max_balls, pool.pending_respawns())
+        };
+
+        if live_balls(world) + pending < max_balls {
+            Some(self.spawn_ball(world))
+        } else {
+            None
+        }
+    }
+
+    fn spawn_ball(&self, world: &mut World) -> Entity {
+        let mut rng = rand::thread_rng();
+
+        let (respawn_comp, ball_type) = {
+            let rules = world.read_resource::<SpawnRules>();
+            (
+                RespawnComponent{max_radius: 400.0, max_speed: rules.max_speed, min_speed: rules.min_speed},
+                rules.choose_ball_type(&mut rng),
+            )
+        };
+        let transform = Transform {
+            pos: Vector2::new(600.0, 600.0),
+            angle: 0.0,
+            scale: Vector2::new(0.25, 0.25)
+        };
+        let bound = BoundingCircle { radius: 28.0 * 0.5 };
+        let ball_sprite = self.types.iter()
+            .find(|&&(sprite_type, _)| sprite_type == ball_type)
+            .expect("No sprite for ball type")
+            .1;
+
+        let entity = world.create_entity()
+            .with(transform)
+            .with(Velocity(Vector2::new(0.0, 0.0)))
+            .with(ball_sprite)
+            .with(respawn_comp)
+            .with(bound)
+            .with(ball_type)
+            .with(RigidBody { mass: 1.0, restitution: 0.9 })
+            .build();
+
+        world.write_resource::<BallEvents>().0.push(BallEvent::Spawned { entity, ball_type });
+
+        entity
+    }
+}
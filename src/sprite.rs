@@ -2,18 +2,27 @@ extern crate nalgebra as na;
 extern crate sdl2;
 extern crate specs;
 
+use assets::Assets;
+use camera::Camera;
 use components::Transform;
 
 use sdl2::rect::{Rect};
 use sdl2::render::{Canvas, RenderTarget, Texture, TextureCreator};
-use sdl2::image::LoadTexture;
-use sdl2::pixels::Color;
+use sdl2::image::ImageRWops;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rwops::RWops;
+use sdl2::surface::Surface;
 use sdl2::ttf::Font;
 use specs::VecStorage;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::rc::Rc;
 
+// Size of the checkerboard that replaces textures that can't be loaded
+const PLACEHOLDER_SIZE: u32 = 32;
+const PLACEHOLDER_SQUARE: u32 = 8;
+
 #[derive(Copy, Clone, Hash, PartialEq, Eq)]
 pub struct TextureId(isize);
 
@@ -32,23 +41,118 @@ impl TextureId {
     }
 }
 
+/// A group of textures that are unloaded together, like the ones that are only
+/// used during a game. Scopes are nested, ending one also ends the ones that
+/// began after it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TextureScope(usize);
+
+/// How many textures are loaded and roughly how much memory they take
+#[derive(Copy, Clone, Debug)]
+pub struct TextureStats {
+    pub textures: usize,
+    pub bytes: usize,
+    pub scopes: usize,
+}
+
+impl fmt::Display for TextureStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "{} textures, {:.1} MiB in {} scopes",
+            self.textures, self.bytes as f32 / (1024. * 1024.), self.scopes
+        )
+    }
+}
+
+struct StoredTexture<'l> {
+    texture: Rc<Texture<'l>>,
+    // Depth of the scope that the texture was made in, 0 for textures that
+    // stay until the game exits
+    scope: usize,
+}
+
 pub struct TextureManager<'l, T: 'l>
 {
     loader: &'l TextureCreator<T>,
     cache: HashMap<String, TextureId>,
     next_key: TextureId,
-    storage: HashMap<TextureId, Rc<Texture<'l>>>,
+    storage: HashMap<TextureId, StoredTexture<'l>>,
+    placeholder: Option<Rc<Texture<'l>>>,
+    assets: Rc<Assets>,
+    // Depth of the innermost scope, new textures are put in it
+    scope: usize,
 }
 
 impl<'l, T> TextureManager<'l, T>
 {
-    pub fn new(loader: &'l TextureCreator<T>) -> Self {
+    pub fn new(loader: &'l TextureCreator<T>, assets: Rc<Assets>) -> Self {
         TextureManager {
             cache: HashMap::new(),
             storage: HashMap::new(),
             next_key: TextureId::new(),
             loader: loader,
+            placeholder: None,
+            assets,
+            scope: 0,
+        }
+    }
+
+    fn insert(&mut self, texture: Rc<Texture<'l>>) -> TextureId {
+        let id = self.next_key;
+        self.next_key = id.next();
+        self.storage.insert(id, StoredTexture { texture, scope: self.scope });
+        id
+    }
+
+    /// Starts a scope. Textures loaded or made until it ends are unloaded
+    /// when it does, unless an outer scope had already loaded them
+    pub fn begin_scope(&mut self) -> TextureScope {
+        self.scope += 1;
+        TextureScope(self.scope)
+    }
+
+    /// Unloads the textures of `scope` and of any scope inside it. Their ids
+    /// must not be used afterwards
+    pub fn end_scope(&mut self, scope: TextureScope) {
+        let unloaded = self.storage.iter()
+            .filter(|&(_, stored)| stored.scope >= scope.0)
+            .map(|(&id, _)| id)
+            .collect::<Vec<_>>();
+        for id in unloaded {
+            self.unload(id);
         }
+        self.scope = scope.0 - 1;
+    }
+
+    /// Unloads a texture before its scope ends, for textures that are only
+    /// shown for a while like text that changed
+    pub fn unload(&mut self, id: TextureId) {
+        self.storage.remove(&id);
+        self.cache.retain(|_, &mut cached| cached != id);
+    }
+
+    pub fn stats(&self) -> TextureStats {
+        // Placeholders share one texture, which is only counted once
+        let mut counted = HashSet::new();
+        let bytes = self.storage.values()
+            .filter(|stored| counted.insert(&*stored.texture as *const Texture))
+            .map(|stored| {
+                let query = stored.texture.query();
+                query.format.byte_size_of_pixels((query.width * query.height) as usize)
+            })
+            .sum();
+        TextureStats { textures: self.storage.len(), bytes, scopes: self.scope }
+    }
+
+    /// Makes a texture from an image file, read through the assets so that it
+    /// can come from the archive
+    fn load_file(&self, path: &str) -> Result<Texture<'l>, String> {
+        let bytes = self.assets.read(path)?;
+        let rwops = RWops::from_bytes(&bytes)?;
+        let surface = rwops.load()?;
+        self.loader
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())
     }
 
     pub fn load(&mut self, path: &str) -> Result<TextureId, String>
@@ -57,16 +161,72 @@ impl<'l, T> TextureManager<'l, T>
             Some(id) => Ok(id),
             None => {
                 println!("Loading {}", path);
-                let resource = Rc::new(self.loader.load_texture(path)?);
-                let id = self.next_key;
+                let resource = Rc::new(self.load_file(path)?);
+                let id = self.insert(resource);
                 self.cache.insert(path.into(), id);
-                self.storage.insert(id, resource.clone());
-                self.next_key = id.next();
                 Ok(id)
             }
         }
     }
 
+    /// Loads a texture, or shows a pink checkerboard in its place if it can't
+    /// be loaded so that one broken image doesn't stop the game
+    pub fn load_or_placeholder(&mut self, path: &str) -> Result<TextureId, String> {
+        match self.load(path) {
+            Ok(id) => Ok(id),
+            Err(e) => {
+                println!("Could not load {}, using a placeholder: {}", path, e);
+                // Each path gets its own id so that it can be reloaded once fixed
+                let placeholder = self.placeholder()?;
+                let id = self.insert(placeholder);
+                self.cache.insert(path.into(), id);
+                Ok(id)
+            }
+        }
+    }
+
+    /// Loads a texture again after its file changed. Sprites keep their id and
+    /// show the new texture. Returns false if the file wasn't loaded before
+    pub fn reload(&mut self, path: &str) -> Result<bool, String> {
+        let id = match self.cache.get(path) {
+            Some(&id) => id,
+            None => return Ok(false),
+        };
+        let resource = self.load_file(path)?;
+        if let Some(stored) = self.storage.get_mut(&id) {
+            stored.texture = Rc::new(resource);
+        }
+        Ok(true)
+    }
+
+    fn placeholder(&mut self) -> Result<Rc<Texture<'l>>, String> {
+        if let Some(ref placeholder) = self.placeholder {
+            return Ok(placeholder.clone());
+        }
+
+        let mut surface = Surface::new(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE, PixelFormatEnum::RGB888)?;
+        surface.fill_rect(None, Color::RGB(0, 0, 0))?;
+        for y in 0..PLACEHOLDER_SIZE / PLACEHOLDER_SQUARE {
+            for x in (0..PLACEHOLDER_SIZE / PLACEHOLDER_SQUARE).filter(|x| (x + y) % 2 == 0) {
+                let square = Rect::new(
+                    (x * PLACEHOLDER_SQUARE) as i32,
+                    (y * PLACEHOLDER_SQUARE) as i32,
+                    PLACEHOLDER_SQUARE,
+                    PLACEHOLDER_SQUARE
+                );
+                surface.fill_rect(square, Color::RGB(255, 0, 255))?;
+            }
+        }
+        let tex = Rc::new(self.loader
+            .create_texture_from_surface(&surface)
+            .map_err(|e| e.to_string())?);
+
+        self.placeholder = Some(tex.clone());
+        Ok(tex)
+    }
+
+    /// Renders `text` into a new texture in the current scope, or into the
+    /// texture `id_to_replace` so that the old text doesn't stay loaded
     pub fn make_text_texture(&mut self, text: &str, font: &Font, id_to_replace: Option<TextureId>) -> Result<TextureId, String> {
         // render a surface, and convert it to a texture bound to the renderer
         let surface = font.render(text)
@@ -76,17 +236,18 @@ impl<'l, T> TextureManager<'l, T>
             .create_texture_from_surface(&surface)
             .map_err(|e| e.to_string())?;
 
-        let id = id_to_replace.unwrap_or_else(|| {
-            let id = self.next_key;
-            self.next_key = id.next();
-            id
-        });
-        self.storage.insert(id, Rc::new(tex));
-        Ok(id)
+        let replaced = id_to_replace.and_then(|id| self.storage.get_mut(&id).map(|stored| (id, stored)));
+        match replaced {
+            Some((id, stored)) => {
+                stored.texture = Rc::new(tex);
+                Ok(id)
+            }
+            None => Ok(self.insert(Rc::new(tex))),
+        }
     }
 
     pub fn get(&self, key: TextureId) -> Option<Rc<Texture<'l>>> {
-        self.storage.get(&key).cloned()
+        self.storage.get(&key).map(|stored| stored.texture.clone())
     }
 }
 
@@ -95,6 +256,7 @@ impl<'l, T> TextureManager<'l, T>
 pub struct Sprite {
     pub texture_id: TextureId,
     pub depth: i32,
+    pub visible: bool,
 }
 
 impl Sprite {
@@ -102,10 +264,12 @@ impl Sprite {
         Sprite {
             texture_id,
             depth: 0,
+            visible: true,
         }
     }
 
-    pub fn draw<T: RenderTarget>(&self, transform: &Transform, canvas: &mut Canvas<T>, texture_manager: &TextureManager<T::Context>) {
+    pub fn draw<T: RenderTarget>(&self, transform: &Transform, camera: &Camera, canvas: &mut Canvas<T>, texture_manager: &TextureManager<T::Context>) {
+        let transform = camera.transform_to_screen(transform);
         let texture = texture_manager.get(self.texture_id).unwrap();
         //calculating the size value
         let sizex = transform.scale.x * texture.query().width as f32;
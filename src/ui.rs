@@ -0,0 +1,134 @@
+use sdl2::keyboard::Keycode;
+
+/// What the player did to a UI this frame
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct UiInput {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub activate: bool,
+    pub back: bool,
+}
+
+impl UiInput {
+    /// UIs are used with the arrow keys, enter and escape
+    pub fn from_keys(pressed: &[Keycode]) -> UiInput {
+        UiInput {
+            up: pressed.contains(&Keycode::Up),
+            down: pressed.contains(&Keycode::Down),
+            left: pressed.contains(&Keycode::Left),
+            right: pressed.contains(&Keycode::Right),
+            activate: pressed.contains(&Keycode::Return) || pressed.contains(&Keycode::Space),
+            back: pressed.contains(&Keycode::Escape),
+        }
+    }
+}
+
+/// The part of a UI that lasts between frames
+#[derive(Clone, Debug, Default)]
+pub struct UiState {
+    // Index of the widget that the keys act on
+    pub focus: usize,
+}
+
+/// Immediate mode UI that is built again every frame. Widgets are added in
+/// order, the keys act on the focused one and each widget returns whether the
+/// player used it. The result is lines of text for a `TextScreen`.
+pub struct Ui<'a> {
+    state: &'a mut UiState,
+    input: UiInput,
+    lines: Vec<String>,
+    widgets: usize,
+}
+
+impl<'a> Ui<'a> {
+    pub fn new(state: &'a mut UiState, input: UiInput) -> Ui<'a> {
+        Ui { state, input, lines: vec!(), widgets: 0 }
+    }
+
+    pub fn back_pressed(&self) -> bool {
+        self.input.back
+    }
+
+    pub fn label(&mut self, text: &str) {
+        self.lines.push(text.to_string());
+    }
+
+    /// Adds a line that can be focused and returns whether it is
+    fn widget(&mut self, text: String) -> bool {
+        let focused = self.widgets == self.state.focus;
+        self.lines.push(if focused { format!("> {} <", text) } else { text });
+        self.widgets += 1;
+        focused
+    }
+
+    pub fn button(&mut self, text: &str) -> bool {
+        self.widget(text.to_string()) && self.input.activate
+    }
+
+    pub fn toggle(&mut self, text: &str, value: &mut bool) -> bool {
+        let label = format!("{}: {}", text, if *value { "on" } else { "off" });
+        let used = self.widget(label) && (self.input.activate || self.input.left || self.input.right);
+        if used {
+            *value = !*value;
+        }
+        used
+    }
+
+    /// Left and right move `value` between 0 and `max` by `step`. The value is
+    /// shown as a percentage
+    pub fn slider(&mut self, text: &str, value: &mut f32, max: f32, step: f32) -> bool {
+        let label = format!("{}: {:.0}%", text, *value * 100.);
+        if !self.widget(label) {
+            return false;
+        }
+
+        let old = *value;
+        if self.input.left {
+            *value -= step;
+        }
+        if self.input.right {
+            *value += step;
+        }
+        // Rounded to whole steps so that repeated steps don't drift
+        *value = ((*value / step).round() * step).max(0.).min(max);
+        *value != old
+    }
+
+    /// Left and right cycle through `options`
+    pub fn choice<T, F>(&mut self, text: &str, value: &mut T, options: &[T], name: F) -> bool
+        where T: Copy + PartialEq, F: Fn(&T) -> String
+    {
+        let label = format!("{}: {}", text, name(value));
+        if !self.widget(label) || options.is_empty() {
+            return false;
+        }
+
+        let index = options.iter().position(|option| option == value).unwrap_or(0);
+        let new_index = if self.input.left {
+            (index + options.len() - 1) % options.len()
+        } else if self.input.right || self.input.activate {
+            (index + 1) % options.len()
+        } else {
+            return false;
+        };
+        *value = options[new_index];
+        true
+    }
+
+    /// Moves the focus for the next frame and returns the lines to show
+    pub fn finish(self) -> Vec<String> {
+        if self.widgets > 0 {
+            let focus = self.state.focus.min(self.widgets - 1);
+            self.state.focus = if self.input.up {
+                (focus + self.widgets - 1) % self.widgets
+            } else if self.input.down {
+                (focus + 1) % self.widgets
+            } else {
+                focus
+            };
+        }
+        self.lines
+    }
+}
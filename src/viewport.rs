@@ -0,0 +1,210 @@
+use nalgebra::Vector2;
+use sdl2::rect::Rect;
+use sdl2::video::FullscreenType;
+
+use constants::*;
+
+/// How the window is shown
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WindowMode {
+    Windowed,
+    // Exclusive fullscreen, which changes the display mode
+    Fullscreen,
+    // A window that covers the whole desktop
+    Borderless,
+}
+
+impl WindowMode {
+    pub fn all() -> [WindowMode; 3] {
+        [WindowMode::Windowed, WindowMode::Fullscreen, WindowMode::Borderless]
+    }
+
+    pub fn from_name(name: &str) -> Option<WindowMode> {
+        WindowMode::all().iter().cloned().find(|mode| mode.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            WindowMode::Windowed => "windowed",
+            WindowMode::Fullscreen => "fullscreen",
+            WindowMode::Borderless => "borderless",
+        }
+    }
+
+    pub fn fullscreen_type(&self) -> FullscreenType {
+        match *self {
+            WindowMode::Windowed => FullscreenType::Off,
+            WindowMode::Fullscreen => FullscreenType::True,
+            WindowMode::Borderless => FullscreenType::Desktop,
+        }
+    }
+}
+
+/// How the game image is fitted into the window
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScaleMode {
+    // The largest whole multiple of the resolution that fits, with black bars
+    // around it. Keeps the pixels sharp and square
+    Integer,
+    // Fills the whole window, pixels get stretched
+    Stretch,
+    // As large as fits without changing the shape of the image, with black bars
+    Aspect,
+}
+
+impl ScaleMode {
+    pub fn all() -> [ScaleMode; 3] {
+        [ScaleMode::Integer, ScaleMode::Stretch, ScaleMode::Aspect]
+    }
+
+    pub fn from_name(name: &str) -> Option<ScaleMode> {
+        ScaleMode::all().iter().cloned().find(|mode| mode.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ScaleMode::Integer => "integer",
+            ScaleMode::Stretch => "stretch",
+            ScaleMode::Aspect => "aspect",
+        }
+    }
+}
+
+/// Resource with the part of the window that the game is drawn in. Updated by
+/// the rendering system whenever it draws
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Viewport {
+    // In output pixels, which are smaller than window coordinates on high DPI
+    // screens
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    // Output pixels per window coordinate
+    pub pixel_ratio: f32,
+}
+
+impl Viewport {
+    /// Where the game goes in an output of `output_size` pixels, for a window
+    /// that is `window_size` big in window coordinates
+    pub fn fit(output_size: (u32, u32), window_size: (u32, u32), mode: ScaleMode) -> Viewport {
+        let (output_width, output_height) = (output_size.0.max(1), output_size.1.max(1));
+        let fit_scale = (output_width as f32 / RESOLUTION.0 as f32).min(output_height as f32 / RESOLUTION.1 as f32);
+
+        let (width, height) = match mode {
+            ScaleMode::Stretch => (output_width, output_height),
+            // Windows smaller than the resolution can't fit a whole multiple, so
+            // they shrink the image like the aspect mode
+            ScaleMode::Integer if fit_scale >= 1. => {
+                let scale = fit_scale.floor() as u32;
+                (RESOLUTION.0 * scale, RESOLUTION.1 * scale)
+            }
+            ScaleMode::Integer | ScaleMode::Aspect => (
+                (RESOLUTION.0 as f32 * fit_scale).round() as u32,
+                (RESOLUTION.1 as f32 * fit_scale).round() as u32,
+            ),
+        };
+
+        Viewport {
+            x: (output_width as i32 - width as i32) / 2,
+            y: (output_height as i32 - height as i32) / 2,
+            width: width.max(1),
+            height: height.max(1),
+            pixel_ratio: output_width as f32 / window_size.0.max(1) as f32,
+        }
+    }
+
+    pub fn rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.width, self.height)
+    }
+
+    /// Output pixels per game pixel, horizontally and vertically
+    pub fn scale(&self) -> Vector2<f32> {
+        Vector2::new(
+            self.width as f32 / RESOLUTION.0 as f32,
+            self.height as f32 / RESOLUTION.1 as f32,
+        )
+    }
+
+    /// Where a point in game pixels ends up in the output
+    pub fn game_to_output(&self, pos: Vector2<f32>) -> Vector2<f32> {
+        let scale = self.scale();
+        Vector2::new(self.x as f32 + pos.x * scale.x, self.y as f32 + pos.y * scale.y)
+    }
+
+    /// The game pixel under a point in window coordinates, like the mouse
+    /// position. Points on the black bars end up outside the game
+    pub fn window_to_game(&self, pos: Vector2<f32>) -> Vector2<f32> {
+        let scale = self.scale();
+        Vector2::new(
+            (pos.x * self.pixel_ratio - self.x as f32) / scale.x,
+            (pos.y * self.pixel_ratio - self.y as f32) / scale.y,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Vector2<f32>, expected: (f32, f32)) {
+        assert!(
+            (actual.x - expected.0).abs() < 0.01 && (actual.y - expected.1).abs() < 0.01,
+            "{:?} is not {:?}", actual, expected
+        );
+    }
+
+    fn viewport(x: i32, y: i32, width: u32, height: u32, pixel_ratio: f32) -> Viewport {
+        Viewport { x, y, width, height, pixel_ratio }
+    }
+
+    #[test]
+    fn integer_scaling() {
+        assert_eq!(Viewport::fit((800, 600), (800, 600), ScaleMode::Integer), viewport(225, 125, 350, 350, 1.));
+        assert_eq!(Viewport::fit((1000, 750), (1000, 750), ScaleMode::Integer), viewport(150, 25, 700, 700, 1.));
+
+        let fitted = Viewport::fit((1000, 750), (1000, 750), ScaleMode::Integer);
+        assert_near(fitted.window_to_game(Vector2::new(150., 25.)), (0., 0.));
+        assert_near(fitted.window_to_game(Vector2::new(500., 375.)), (175., 175.));
+    }
+
+    #[test]
+    fn stretch_scaling() {
+        let fitted = Viewport::fit((700, 350), (700, 350), ScaleMode::Stretch);
+        assert_eq!(fitted, viewport(0, 0, 700, 350, 1.));
+        assert_near(fitted.window_to_game(Vector2::new(350., 175.)), (175., 175.));
+        assert_near(fitted.window_to_game(Vector2::new(700., 350.)), (350., 350.));
+    }
+
+    #[test]
+    fn aspect_scaling() {
+        let fitted = Viewport::fit((700, 500), (700, 500), ScaleMode::Aspect);
+        assert_eq!(fitted, viewport(100, 0, 500, 500, 1.));
+        assert_near(fitted.window_to_game(Vector2::new(100., 0.)), (0., 0.));
+        assert_near(fitted.window_to_game(Vector2::new(600., 500.)), (350., 350.));
+        // On the black bars
+        assert!(fitted.window_to_game(Vector2::new(50., 250.)).x < 0.);
+        assert!(fitted.window_to_game(Vector2::new(650., 250.)).x > 350.);
+    }
+
+    #[test]
+    fn windows_smaller_than_the_resolution() {
+        // Integer scaling shrinks the image like aspect scaling
+        let fitted = Viewport::fit((200, 100), (200, 100), ScaleMode::Integer);
+        assert_eq!(fitted, viewport(50, 0, 100, 100, 1.));
+        assert_eq!(Viewport::fit((200, 100), (200, 100), ScaleMode::Aspect), fitted);
+        assert_near(fitted.window_to_game(Vector2::new(100., 50.)), (175., 175.));
+        // Minimised windows can report a size of 0
+        assert_eq!(Viewport::fit((0, 0), (0, 0), ScaleMode::Integer).width, 1);
+    }
+
+    #[test]
+    fn high_dpi() {
+        // The output has twice as many pixels as the window has coordinates
+        let fitted = Viewport::fit((1400, 1000), (700, 500), ScaleMode::Aspect);
+        assert_eq!(fitted, viewport(200, 0, 1000, 1000, 2.));
+        assert_near(fitted.window_to_game(Vector2::new(100., 0.)), (0., 0.));
+        assert_near(fitted.window_to_game(Vector2::new(350., 250.)), (175., 175.));
+        assert_near(fitted.game_to_output(Vector2::new(175., 175.)), (700., 500.));
+    }
+}
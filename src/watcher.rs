@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+use std::fs;
+use std::time::SystemTime;
+
+// Seconds between checks of the files, looking at every file each frame would
+// be wasteful
+const POLL_INTERVAL: f32 = 0.5;
+
+/// Notices when files change by polling their modification times, so that
+/// assets can be reloaded while the game runs
+pub struct FileWatcher {
+    // Modification time of each file when it was last checked, None if it
+    // couldn't be read
+    modified: HashMap<String, Option<SystemTime>>,
+    last_poll: f32,
+}
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+impl FileWatcher {
+    pub fn new(paths: &[String]) -> FileWatcher {
+        FileWatcher {
+            modified: paths.iter().map(|path| (path.clone(), modified(path))).collect(),
+            last_poll: 0.,
+        }
+    }
+
+    /// The files that changed since the last poll. Files that were deleted are
+    /// reported once they come back, since editors often save by replacing them
+    pub fn poll(&mut self, curr_time: f32) -> Vec<String> {
+        if curr_time - self.last_poll < POLL_INTERVAL {
+            return vec!();
+        }
+        self.last_poll = curr_time;
+
+        let mut changed = vec!();
+        for (path, last_modified) in &mut self.modified {
+            let now_modified = modified(path);
+            if now_modified != *last_modified {
+                *last_modified = now_modified;
+                if now_modified.is_some() {
+                    changed.push(path.clone());
+                }
+            }
+        }
+        changed.sort();
+        changed
+    }
+}